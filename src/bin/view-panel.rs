@@ -1,8 +1,114 @@
 use iced;
+use std::path::Path;
+use cfl_view::cfl_buffer::{parse_voxel_size, CflBuffer};
+use cfl_view::dicom::{is_dicom, read_dicom, read_dicom_groups};
+use cfl_view::raw::{parse_shape, Endianness, RawFormat, RawLayout, RawType};
 use cfl_view::view_panel::ViewPanel;
+use cfl_view::ViewError;
 
 fn main() -> iced::Result {
 
-    iced::run(ViewPanel::update,ViewPanel::view)
+    iced::application(boot,ViewPanel::update,ViewPanel::view)
+        .subscription(ViewPanel::subscription)
+        .run()
 
-}
\ No newline at end of file
+}
+
+/// opens the cfl, nifti, numpy, matlab or ismrmrd file given as the first argument, or a blank image. A
+/// directory is read as a dicom series. Any further files, and any further arrays of a .npz or .mat, are
+/// shown below it for comparison. A voxel size given on the command line overrides the one read from the
+/// files. Giving a shape with --raw reads every file as a headerless array instead, and --var reads only the
+/// named array of .npz and .mat files. A dicom directory holding several series needs --series to pick one
+/// of the groups listed when opening it
+///
+/// usage: view-panel [cfl|nii|npy|npz|mat|h5|dcm|dir] [compare file ...] [--voxel-size 0.5,0.5,2]
+///        view-panel raw [compare raw ...] --raw 256,256,64 [--dtype f32] [--layout real|interleaved|split]
+///                   [--big-endian] [--offset bytes]
+///        view-panel data.mat [--var kspace]
+///        view-panel dicom_dir [--series 1]
+fn boot() -> ViewPanel {
+    let mut paths = vec![];
+    let mut voxel_size = None;
+    let mut raw = None;
+    let mut var = None;
+    let mut series = None;
+    let mut raw_format = RawFormat::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--voxel-size" => {
+                let sizes = args.next().expect("--voxel-size requires a comma separated list of sizes");
+                voxel_size = Some(parse_voxel_size(sizes.split(',')).expect("invalid voxel size"));
+            }
+            "--raw" => {
+                let shape = args.next().expect("--raw requires a comma separated list of sizes");
+                raw = Some(parse_shape(shape.split(',')).expect("invalid raw shape"));
+            }
+            "--dtype" => {
+                let dtype = args.next().expect("--dtype requires a type such as f32 or i16");
+                raw_format.dtype = RawType::parse(&dtype).expect("invalid dtype");
+            }
+            "--layout" => {
+                let layout = args.next().expect("--layout requires real, interleaved or split");
+                raw_format.layout = RawLayout::parse(&layout).expect("invalid layout");
+            }
+            "--var" => var = Some(args.next().expect("--var requires an array name")),
+            "--series" => {
+                let index = args.next().expect("--series requires the number of a dicom series");
+                series = Some(index.parse::<usize>().expect("invalid series"));
+            }
+            "--big-endian" => raw_format.endianness = Endianness::Big,
+            "--offset" => {
+                let offset = args.next().expect("--offset requires a number of bytes");
+                raw_format.offset = offset.parse().expect("invalid offset");
+            }
+            _ => paths.push(arg),
+        }
+    }
+    let mut dicom_info = vec![];
+    let mut open = |path:&String| {
+        let name = Path::new(path).file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+        match (raw,&var) {
+            (Some(shape),_) => RawFormat { shape, ..raw_format }.read(path).map(|cfl_buffer| vec![(name,cfl_buffer)]),
+            (None,Some(var)) => CflBuffer::open_variable(path,var).map(|cfl_buffer| vec![(format!("{}/{}",name,var),cfl_buffer)]),
+            (None,None) if is_dicom(path) => {
+                let stacked = match series {
+                    Some(series) => read_dicom_groups(path)
+                        .and_then(|groups| groups.get(series).ok_or(ViewError::BadIndex(series))?.stack()),
+                    None => read_dicom(path),
+                };
+                stacked.map(|(cfl_buffer,info)|{
+                    dicom_info.push((name.clone(),info));
+                    vec![(name,cfl_buffer)]
+                })
+            }
+            (None,None) => CflBuffer::open_all(path),
+        }
+    };
+    let mut buffers:Vec<_> = paths.iter()
+        .flat_map(|path| open(path).unwrap_or_else(|e|{
+            eprintln!("couldn't open {}: {:?}",path,e);
+            std::process::exit(1)
+        }))
+        .collect();
+    if buffers.is_empty() {
+        buffers.push((String::new(),CflBuffer::default()));
+    }
+    if let Some(voxel_size) = voxel_size {
+        buffers.iter_mut().for_each(|(_,cfl_buffer)|{
+            cfl_buffer.voxel_size = voxel_size;
+            cfl_buffer.geometry_known = true;
+        });
+    }
+    let mut buffers = buffers.into_iter();
+    let (name,cfl_buffer) = buffers.next().expect("there is at least one buffer");
+    let mut view_panel = ViewPanel::from(cfl_buffer);
+    view_panel.set_name(name);
+    for (name,cfl_buffer) in buffers {
+        view_panel.add_compare(name,cfl_buffer);
+    }
+    for (name,info) in dicom_info {
+        view_panel.add_dicom_info(name,info);
+    }
+    view_panel
+}
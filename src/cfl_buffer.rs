@@ -0,0 +1,300 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use array_lib::ArrayDim;
+use array_lib::cfl::num_complex::Complex32;
+use crate::dicom::{is_dicom, read_dicom};
+use crate::nifti::{is_nifti, read_nifti};
+use crate::ismrmrd::read_ismrmrd;
+use crate::mat::{read_mat, read_mat_all};
+use crate::npy::{read_npy, read_npz};
+use crate::slice::N_DIMS;
+use crate::ViewError;
+
+/// size of the blank default image
+pub const DEFAULT_DIMS:usize = 128;
+
+/// extension of the voxel size sidecar written next to a cfl
+pub const VOXEL_SIZE_EXT:&str = "vox";
+
+/// full cfl array held in memory
+pub struct CflBuffer {
+    pub data: Vec<Complex32>,
+    pub dims: ArrayDim,
+    /// physical size of a sample along each dimension in mm
+    pub voxel_size: [f32;N_DIMS],
+    /// true if the voxel size was read from the file or given by the user, rather than left at 1 mm
+    pub geometry_known: bool,
+    /// maps an index along the first three dimensions to scanner coordinates in mm, for data read from a
+    /// format that stores orientation
+    pub affine: Option<[[f32;4];3]>,
+}
+
+impl Default for CflBuffer {
+
+    /// default buffer holds a blank 128x128 image
+    fn default() -> CflBuffer {
+        let dims = ArrayDim::from_shape(&[DEFAULT_DIMS,DEFAULT_DIMS]);
+        CflBuffer::new(vec![Complex32::ZERO;dims.numel()],dims)
+    }
+}
+
+impl CflBuffer {
+
+    /// buffer with isotropic 1 mm voxels
+    pub fn new(data:Vec<Complex32>, dims:ArrayDim) -> CflBuffer {
+        CflBuffer {
+            data,
+            dims,
+            voxel_size: [1.;N_DIMS],
+            geometry_known: false,
+            affine: None,
+        }
+    }
+
+    /// reads a nifti, numpy, matlab or ismrmrd file, a dicom file or directory of them, or a .cfl/.hdr pair for
+    /// any other path. Only the first array of a .npz or .mat is read
+    pub fn open(path:impl AsRef<Path>) -> Result<CflBuffer,ViewError> {
+        let path = path.as_ref();
+        if is_nifti(path) {
+            return read_nifti(path)
+        }
+        if is_dicom(path) {
+            return read_dicom(path).map(|(cfl_buffer,_)| cfl_buffer)
+        }
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("npy") => read_npy(path),
+            Some("npz") => read_npz(path)?.into_iter().next()
+                .map(|(_,cfl_buffer)| cfl_buffer)
+                .ok_or(ViewError::Parse(format!("{} holds no arrays",path.display()))),
+            Some("mat") => read_mat_all(path)?.into_iter().next()
+                .map(|(_,cfl_buffer)| cfl_buffer)
+                .ok_or(ViewError::Parse(format!("{} holds no numeric arrays",path.display()))),
+            Some("h5") => read_ismrmrd(path),
+            _ => CflBuffer::from_cfl(path),
+        }
+    }
+
+    /// reads every array in a file with a name for each. Only .npz and .mat files hold more than one
+    pub fn open_all(path:impl AsRef<Path>) -> Result<Vec<(String,CflBuffer)>,ViewError> {
+        let path = path.as_ref();
+        let name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+        let arrays = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("npz") => read_npz(path)?,
+            Some("mat") => read_mat_all(path)?,
+            _ => return Ok(vec![(name,CflBuffer::open(path)?)]),
+        };
+        Ok(arrays.into_iter().map(|(array,cfl_buffer)| (format!("{}/{}",name,array),cfl_buffer)).collect())
+    }
+
+    /// reads a named array of a .npz or .mat file
+    pub fn open_variable(path:impl AsRef<Path>, name:&str) -> Result<CflBuffer,ViewError> {
+        let path = path.as_ref();
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("npz") => read_npz(path)?.into_iter()
+                .find(|(array,_)| array == name)
+                .map(|(_,cfl_buffer)| cfl_buffer)
+                .ok_or(ViewError::Parse(format!("{} has no array {}",path.display(),name))),
+            Some("mat") => read_mat(path,name),
+            _ => Err(ViewError::Parse(format!("{} has no named arrays",path.display()))),
+        }
+    }
+
+    /// reads a .cfl/.hdr pair from disk, given either file or the name without an extension. The .cfl must
+    /// hold exactly the samples the .hdr describes. Voxel sizes are read from a .vox sidecar if there is one,
+    /// and a sidecar that can't be parsed is an error
+    pub fn from_cfl(path:impl AsRef<Path>) -> Result<CflBuffer,ViewError> {
+        let header = std::fs::read_to_string(cfl_path(path.as_ref(),"hdr"))?;
+        let shape:Vec<usize> = header.lines()
+            .filter(|line| !line.trim_start().starts_with('#'))
+            .flat_map(|line| line.split_whitespace())
+            .map(|size| size.parse().map_err(|_| ViewError::Parse(size.to_string())))
+            .collect::<Result<_,_>>()?;
+        if shape.is_empty() || shape.len() > N_DIMS {
+            return Err(ViewError::Parse(format!("cfl header with {} dimensions",shape.len())));
+        }
+        if shape.contains(&0) {
+            return Err(ViewError::Parse(format!("cfl header with an empty dimension: {:?}",shape)));
+        }
+        let dims = ArrayDim::from_shape(&shape);
+        let file = File::open(cfl_path(path.as_ref(),"cfl"))?;
+        let needed = dims.numel() * 8;
+        let got = file.metadata()?.len() as usize;
+        if got != needed {
+            return Err(ViewError::BufferSize { needed, got });
+        }
+        let mut bytes = vec![0u8;needed];
+        BufReader::new(file).read_exact(&mut bytes)?;
+        let data = bytes.chunks_exact(8)
+            .map(|b| Complex32::new(f32::from_le_bytes([b[0],b[1],b[2],b[3]]),f32::from_le_bytes([b[4],b[5],b[6],b[7]])))
+            .collect();
+        let mut cfl_buffer = CflBuffer::new(data,dims);
+        let sidecar = cfl_path(path.as_ref(),VOXEL_SIZE_EXT);
+        if sidecar.exists() {
+            cfl_buffer.voxel_size = read_voxel_size(&sidecar)
+                .map_err(|e| ViewError::Parse(format!("voxel size sidecar {}: {:?}",sidecar.display(),e)))?;
+            cfl_buffer.geometry_known = true;
+        }
+        Ok(cfl_buffer)
+    }
+
+    /// writes a .cfl/.hdr pair that BART can read, with a .vox sidecar if the voxels aren't 1 mm. A .cfl or
+    /// .hdr extension on the path is replaced
+    pub fn write_cfl(&self, path:impl AsRef<Path>) -> Result<(),ViewError> {
+        let path = path.as_ref();
+        if self.data.len() != self.dims.numel() {
+            return Err(ViewError::BufferSize { needed: self.dims.numel(), got: self.data.len() });
+        }
+
+        let shape:Vec<String> = self.dims.shape().iter().map(|n| n.to_string()).collect();
+        std::fs::write(cfl_path(path,"hdr"),format!("# Dimensions\n{}\n",shape.join(" ")))?;
+
+        let mut writer = BufWriter::new(File::create(cfl_path(path,"cfl"))?);
+        for x in &self.data {
+            writer.write_all(&x.re.to_le_bytes())?;
+            writer.write_all(&x.im.to_le_bytes())?;
+        }
+        writer.flush()?;
+
+        let sidecar = cfl_path(path,VOXEL_SIZE_EXT);
+        if self.voxel_size.iter().any(|&size| size != 1.) {
+            let sizes:Vec<String> = self.voxel_size.iter().map(|size| size.to_string()).collect();
+            std::fs::write(sidecar,format!("# Voxel size\n{}\n",sizes.join(" ")))?;
+        }
+        Ok(())
+    }
+
+    /// copies the block from `start` up to but not including `end` along every dimension, like `bart extract`
+    pub fn crop(&self, start:&[usize;N_DIMS], end:&[usize;N_DIMS]) -> Result<CflBuffer,ViewError> {
+        let shape = self.dims.shape();
+        let mut out_shape = [1;N_DIMS];
+        for dim in 0..N_DIMS {
+            if start[dim] >= end[dim] {
+                return Err(ViewError::BadIndex(dim));
+            }
+            if end[dim] > shape[dim] {
+                return Err(ViewError::IndexOutOfBounds { dim, index: end[dim] - 1, size: shape[dim] });
+            }
+            out_shape[dim] = end[dim] - start[dim];
+        }
+        let dims = ArrayDim::from_shape(&out_shape);
+        let mut idx = [0;N_DIMS];
+        let data = (0..dims.numel()).map(|i|{
+            // column-major, so the first dim varies fastest
+            let mut rem = i;
+            for dim in 0..N_DIMS {
+                idx[dim] = start[dim] + rem % out_shape[dim];
+                rem /= out_shape[dim];
+            }
+            self.data[self.dims.calc_addr(&idx)]
+        }).collect();
+        // the first sample of the crop becomes the origin
+        let affine = self.affine.map(|mut affine|{
+            for row in affine.iter_mut() {
+                row[3] += (0..3).map(|dim| row[dim] * start[dim] as f32).sum::<f32>();
+            }
+            affine
+        });
+        Ok(CflBuffer { data, dims, voxel_size: self.voxel_size, geometry_known: self.geometry_known, affine })
+    }
+
+    /// scanner coordinates in mm of a sample, if the orientation is known
+    pub fn world_position(&self, idx:&[usize;N_DIMS]) -> Option<[f32;3]> {
+        let affine = self.affine?;
+        Some(affine.map(|row| (0..3).map(|dim| row[dim] * idx[dim] as f32).sum::<f32>() + row[3]))
+    }
+
+    /// the sample at a full cfl index, or None if the index is out of bounds
+    pub fn get(&self, idx:&[usize;N_DIMS]) -> Option<Complex32> {
+        if idx.iter().zip(self.dims.shape().iter()).any(|(&i,&n)| i >= n) {
+            return None
+        }
+        self.data.get(self.dims.calc_addr(idx)).copied()
+    }
+
+}
+
+/// the path with `ext` in place of a trailing .cfl or .hdr. Any other dots belong to the name, so
+/// scan_0.5mm.cfl becomes scan_0.5mm.hdr and not scan_0.hdr
+fn cfl_path(path:&Path, ext:&str) -> PathBuf {
+    let name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+    let stem = name.strip_suffix(".cfl").or(name.strip_suffix(".hdr")).unwrap_or(&name);
+    path.with_file_name(format!("{}.{}",stem,ext))
+}
+
+/// reads per-dimension voxel sizes in mm from a sidecar file. The layout follows the .hdr file, with lines
+/// starting with '#' ignored and the sizes separated by whitespace. Missing dimensions default to 1 mm.
+pub fn read_voxel_size(path:impl AsRef<Path>) -> Result<[f32;N_DIMS],ViewError> {
+    let contents = std::fs::read_to_string(path)?;
+    let values = contents.lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .flat_map(|line| line.split_whitespace());
+    parse_voxel_size(values)
+}
+
+/// parses voxel sizes from a list of values, such as the comma separated list given on the command line
+pub fn parse_voxel_size<'a>(values:impl IntoIterator<Item=&'a str>) -> Result<[f32;N_DIMS],ViewError> {
+    let mut voxel_size = [1.;N_DIMS];
+    for (i,value) in values.into_iter().enumerate() {
+        if i >= N_DIMS {
+            return Err(ViewError::BadIndex(i));
+        }
+        let size:f32 = value.trim().parse().map_err(|_| ViewError::Parse(value.to_string()))?;
+        if !(size > 0. && size.is_finite()) {
+            return Err(ViewError::Parse(value.to_string()));
+        }
+        voxel_size[i] = size;
+    }
+    Ok(voxel_size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dotted_name_round_trip() {
+        let dir = std::env::temp_dir().join(format!("cfl_view_dotted_{}",std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let dims = ArrayDim::from_shape(&[2,3]);
+        let mut cfl_buffer = CflBuffer::new((0..6).map(|i| Complex32::new(i as f32,-(i as f32))).collect(),dims);
+        cfl_buffer.voxel_size[2] = 0.5;
+        cfl_buffer.write_cfl(dir.join("scan_0.5mm.cfl")).unwrap();
+        for ext in ["cfl","hdr","vox"] {
+            assert!(dir.join(format!("scan_0.5mm.{}",ext)).exists());
+            assert!(!dir.join(format!("scan_0.{}",ext)).exists());
+        }
+        for path in ["scan_0.5mm","scan_0.5mm.cfl","scan_0.5mm.hdr"] {
+            let read = CflBuffer::from_cfl(dir.join(path)).unwrap();
+            assert_eq!(read.dims.shape(),dims.shape());
+            assert_eq!(read.data,cfl_buffer.data);
+            assert_eq!(read.voxel_size,cfl_buffer.voxel_size);
+            assert!(read.geometry_known);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn bad_sidecar_is_an_error() {
+        let dir = std::env::temp_dir().join(format!("cfl_view_sidecar_{}",std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("scan");
+        CflBuffer::default().write_cfl(&path).unwrap();
+        assert!(!CflBuffer::from_cfl(&path).unwrap().geometry_known);
+        std::fs::write(dir.join("scan.vox"),"# Voxel size
+1 -2
+").unwrap();
+        assert!(matches!(CflBuffer::from_cfl(&path),Err(ViewError::Parse(_))));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn empty_dimension_is_an_error() {
+        let dir = std::env::temp_dir().join(format!("cfl_view_empty_{}",std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("empty.hdr"),"# Dimensions\n4 0 1\n").unwrap();
+        std::fs::write(dir.join("empty.cfl"),[]).unwrap();
+        assert!(matches!(CflBuffer::from_cfl(dir.join("empty")),Err(ViewError::Parse(_))));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
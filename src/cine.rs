@@ -0,0 +1,137 @@
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+
+/// what happens when playback reaches the end of the dimension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CineMode {
+    /// jump back to the first frame
+    Loop,
+    /// reverse direction
+    Bounce,
+}
+
+impl CineMode {
+    pub const ALL: [CineMode;2] = [CineMode::Loop, CineMode::Bounce];
+}
+
+impl Display for CineMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CineMode::Loop => write!(f, "loop"),
+            CineMode::Bounce => write!(f, "bounce"),
+        }
+    }
+}
+
+/// steps the slice index of one cfl dimension over time
+#[derive(Debug, Clone, Copy)]
+pub struct Cine {
+    /// the cfl dimension to play through
+    pub dim: usize,
+    /// frames per second
+    pub fps: f32,
+    pub mode: CineMode,
+    pub playing: bool,
+    /// direction of travel for bounce mode
+    forward: bool,
+}
+
+impl Default for Cine {
+    fn default() -> Cine {
+        Cine {
+            dim: 2,
+            fps: 10.,
+            mode: CineMode::Loop,
+            playing: false,
+            forward: true,
+        }
+    }
+}
+
+impl Cine {
+
+    /// time between frames
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs_f32(1. / self.fps.max(0.1))
+    }
+
+    /// the frames of one full playback cycle through a dimension of size `n`, starting at the first frame
+    pub fn cycle(&self, n:usize) -> Vec<usize> {
+        if n == 0 {
+            return vec![]
+        }
+        let len = match self.mode {
+            CineMode::Loop => n,
+            CineMode::Bounce => (2 * n).saturating_sub(2).max(1),
+        };
+        let mut cine = Cine { forward: true, ..*self };
+        let mut frames = vec![0];
+        while frames.len() < len {
+            let next = cine.next_frame(frames[frames.len() - 1],n);
+            frames.push(next);
+        }
+        frames
+    }
+
+    /// returns the frame after `current` for a dimension of size `n`
+    pub fn next_frame(&mut self, current:usize, n:usize) -> usize {
+        if n <= 1 {
+            return 0
+        }
+        let current = current.min(n - 1);
+        match self.mode {
+            CineMode::Loop => {
+                self.forward = true;
+                (current + 1) % n
+            }
+            CineMode::Bounce => {
+                if self.forward && current + 1 >= n {
+                    self.forward = false;
+                }else if !self.forward && current == 0 {
+                    self.forward = true;
+                }
+                if self.forward { current + 1 } else { current - 1 }
+            }
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play(mode:CineMode, n:usize, steps:usize) -> Vec<usize> {
+        let mut cine = Cine { mode, ..Cine::default() };
+        let mut frames = vec![0];
+        for _ in 0..steps {
+            let next = cine.next_frame(frames[frames.len() - 1],n);
+            frames.push(next);
+        }
+        frames
+    }
+
+    #[test]
+    fn loop_order() {
+        assert_eq!(play(CineMode::Loop,3,7),[0,1,2,0,1,2,0,1]);
+        assert_eq!(Cine::default().cycle(4),[0,1,2,3]);
+    }
+
+    #[test]
+    fn bounce_order() {
+        assert_eq!(play(CineMode::Bounce,3,8),[0,1,2,1,0,1,2,1,0]);
+        let cine = Cine { mode:CineMode::Bounce, ..Cine::default() };
+        assert_eq!(cine.cycle(4),[0,1,2,3,2,1]);
+        assert_eq!(cine.cycle(1),[0]);
+        assert!(cine.cycle(0).is_empty());
+    }
+
+    #[test]
+    fn single_frame_and_out_of_range() {
+        assert_eq!(play(CineMode::Bounce,1,3),[0,0,0,0]);
+        let mut cine = Cine::default();
+        assert_eq!(cine.next_frame(9,3),0);
+        cine.mode = CineMode::Bounce;
+        assert_eq!(cine.next_frame(9,3),1);
+    }
+}
@@ -0,0 +1,749 @@
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use array_lib::ArrayDim;
+use array_lib::cfl::num_complex::Complex32;
+use flate2::read::DeflateDecoder;
+use rayon::prelude::*;
+use crate::cfl_buffer::CflBuffer;
+use crate::raw::{le, Endianness, RawType};
+use crate::slice::N_DIMS;
+use crate::ViewError;
+
+/// cfl dimensions the images of a series are stacked along, after the rows and columns of each image
+pub const SLICE_DIM:usize = 2;
+pub const ECHO_DIM:usize = 5;
+pub const TIME_DIM:usize = 10;
+
+const PREAMBLE_LEN:usize = 128;
+const DICOM_MAGIC:&[u8] = b"DICM";
+
+/// slice positions in mm and echo times in ms closer than this are the same
+const TOLERANCE:f32 = 1e-2;
+
+const IMPLICIT_LITTLE:&str = "1.2.840.10008.1.2";
+const EXPLICIT_LITTLE:&str = "1.2.840.10008.1.2.1";
+const DEFLATED_LITTLE:&str = "1.2.840.10008.1.2.1.99";
+const EXPLICIT_BIG:&str = "1.2.840.10008.1.2.2";
+
+/// group and element number of a data element
+type Tag = (u16,u16);
+
+const TRANSFER_SYNTAX:Tag = (0x0002,0x0010);
+const IMAGE_TYPE:Tag = (0x0008,0x0008);
+const SERIES_DESCRIPTION:Tag = (0x0008,0x103E);
+const SLICE_THICKNESS:Tag = (0x0018,0x0050);
+const REPETITION_TIME:Tag = (0x0018,0x0080);
+const ECHO_TIME:Tag = (0x0018,0x0081);
+const ECHO_NUMBER:Tag = (0x0018,0x0086);
+const SERIES_UID:Tag = (0x0020,0x000E);
+const INSTANCE_NUMBER:Tag = (0x0020,0x0013);
+const IMAGE_POSITION:Tag = (0x0020,0x0032);
+const IMAGE_ORIENTATION:Tag = (0x0020,0x0037);
+const TEMPORAL_POSITION:Tag = (0x0020,0x0100);
+const SLICE_LOCATION:Tag = (0x0020,0x1041);
+const SAMPLES_PER_PIXEL:Tag = (0x0028,0x0002);
+const NUMBER_OF_FRAMES:Tag = (0x0028,0x0008);
+const ROWS:Tag = (0x0028,0x0010);
+const COLUMNS:Tag = (0x0028,0x0011);
+const PIXEL_SPACING:Tag = (0x0028,0x0030);
+const BITS_ALLOCATED:Tag = (0x0028,0x0100);
+const BITS_STORED:Tag = (0x0028,0x0101);
+const PIXEL_REPRESENTATION:Tag = (0x0028,0x0103);
+const RESCALE_INTERCEPT:Tag = (0x0028,0x1052);
+const RESCALE_SLOPE:Tag = (0x0028,0x1053);
+const PIXEL_DATA:Tag = (0x7FE0,0x0010);
+const ITEM:Tag = (0xFFFE,0xE000);
+const ITEM_END:Tag = (0xFFFE,0xE00D);
+const SEQUENCE_END:Tag = (0xFFFE,0xE0DD);
+const UNDEFINED_LENGTH:u32 = 0xFFFF_FFFF;
+
+/// one image of a series, with the tags that place it in the stack
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DicomImage {
+    pub rows: usize,
+    pub columns: usize,
+    /// rescaled pixel values row by row, or the phase in radians for phase images
+    pub pixels: Vec<f32>,
+    pub phase: bool,
+    pub series_uid: String,
+    pub series_description: String,
+    /// spacing between rows and between columns in mm
+    pub pixel_spacing: Option<[f32;2]>,
+    pub slice_thickness: Option<f32>,
+    /// in ms
+    pub repetition_time: Option<f32>,
+    /// in ms
+    pub echo_time: Option<f32>,
+    pub echo_number: Option<i32>,
+    pub instance_number: Option<i32>,
+    pub temporal_position: Option<i32>,
+    /// patient coordinates of the first pixel in mm
+    pub position: Option<[f32;3]>,
+    /// direction cosines along a row and down a column
+    pub orientation: Option<[f32;6]>,
+    pub slice_location: Option<f32>,
+}
+
+impl DicomImage {
+
+    /// distance of the image along the normal of its stack, falling back to the slice location and then the
+    /// instance number
+    pub fn slice_position(&self, normal:Option<[f32;3]>) -> f32 {
+        match (self.position,normal) {
+            (Some(position),Some(normal)) => (0..3).map(|k| position[k] * normal[k]).sum(),
+            _ => self.slice_location.unwrap_or(self.instance_number.unwrap_or(0) as f32),
+        }
+    }
+
+    /// true for images of the same series, size and orientation
+    fn stacks_with(&self, other:&DicomImage) -> bool {
+        self.series_uid == other.series_uid
+            && (self.rows,self.columns) == (other.rows,other.columns)
+            && same_orientation(self.orientation,other.orientation)
+    }
+
+    /// the echo time, or the echo number for images without one
+    fn echo(&self) -> f32 {
+        self.echo_time.unwrap_or(self.echo_number.unwrap_or(0) as f32)
+    }
+
+}
+
+/// tags of a series shown alongside its images
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DicomInfo {
+    /// the distinct descriptions, as magnitude and phase are often separate series
+    pub series_descriptions: Vec<String>,
+    /// spacing of columns, rows and slices in mm
+    pub voxel_size: [f32;3],
+    pub slice_thickness: Option<f32>,
+    /// in ms, one per index along the echo dimension
+    pub echo_times: Vec<f32>,
+    pub repetition_time: Option<f32>,
+    pub n_images: usize,
+    pub magnitude: bool,
+    pub phase: bool,
+}
+
+impl Display for DicomInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "series: {}", self.series_descriptions.join(", "))?;
+        writeln!(f, "images: {} {}", self.n_images, kind(self.magnitude,self.phase))?;
+        write!(f, "voxel: {:.3} x {:.3} x {:.3} mm", self.voxel_size[0], self.voxel_size[1], self.voxel_size[2])?;
+        if let Some(thickness) = self.slice_thickness {
+            write!(f, "\nthickness: {:.3} mm", thickness)?;
+        }
+        if !self.echo_times.is_empty() {
+            let echo_times:Vec<String> = self.echo_times.iter().map(|te| format!("{:.2}",te)).collect();
+            write!(f, "\nTE: {} ms", echo_times.join(", "))?;
+        }
+        if let Some(tr) = self.repetition_time {
+            write!(f, "\nTR: {:.2} ms", tr)?;
+        }
+        Ok(())
+    }
+}
+
+/// images that stack into one array. Images are grouped by series and orientation, as a localizer series
+/// holds several planes, and a magnitude-only group is paired with a phase-only group of the same geometry
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DicomGroup {
+    pub images: Vec<DicomImage>,
+}
+
+impl DicomGroup {
+
+    pub fn stack(&self) -> Result<(CflBuffer,DicomInfo),ViewError> {
+        stack_dicom(&self.images)
+    }
+
+    /// the distinct slice positions and echoes of the group, as they are placed by stack_dicom
+    fn positions(&self) -> (Vec<f32>,Vec<f32>) {
+        let normal = self.images.first().and_then(|image| image.orientation).map(|orientation| normal(&orientation));
+        let slices = distinct(self.images.iter().map(|image| image.slice_position(normal)));
+        let echoes = distinct(self.images.iter().map(|image| image.echo()));
+        (slices,echoes)
+    }
+
+    /// true if the phase images of `other` belong at the places of the magnitude images of this group
+    fn pairs_with(&self, other:&DicomGroup) -> bool {
+        let (Some(a),Some(b)) = (self.images.first(),other.images.first()) else {
+            return false
+        };
+        let close = |a:&[f32],b:&[f32]| a.len() == b.len() && a.iter().zip(b).all(|(a,b)| (a - b).abs() < TOLERANCE);
+        let ((slices_a,echoes_a),(slices_b,echoes_b)) = (self.positions(),other.positions());
+        self.images.iter().all(|image| !image.phase)
+            && other.images.iter().all(|image| image.phase)
+            && (a.rows,a.columns) == (b.rows,b.columns)
+            && same_orientation(a.orientation,b.orientation)
+            && close(&slices_a,&slices_b)
+            && close(&echoes_a,&echoes_b)
+    }
+
+}
+
+impl Display for DicomGroup {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut descriptions:Vec<&str> = vec![];
+        for image in &self.images {
+            if !descriptions.contains(&image.series_description.as_str()) {
+                descriptions.push(&image.series_description);
+            }
+        }
+        let magnitude = self.images.iter().any(|image| !image.phase);
+        let phase = self.images.iter().any(|image| image.phase);
+        write!(f, "{}: {} {} images", descriptions.join(", "), self.images.len(), kind(magnitude,phase))
+    }
+}
+
+/// true for directories, which are read as a series, and single .dcm or .ima files
+pub fn is_dicom(path:impl AsRef<Path>) -> bool {
+    let path = path.as_ref();
+    path.is_dir() || path.extension().and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("dcm") || ext.eq_ignore_ascii_case("ima"))
+}
+
+/// reads the images of a directory, or a single dicom file, stacked into one cfl. Directories holding more
+/// than one group of images are an error listing the groups, which are read with read_dicom_groups
+pub fn read_dicom(path:impl AsRef<Path>) -> Result<(CflBuffer,DicomInfo),ViewError> {
+    let path = path.as_ref();
+    let groups = read_dicom_groups(path)?;
+    if let [group] = groups.as_slice() {
+        return group.stack()
+    }
+    let groups:Vec<String> = groups.iter().enumerate().map(|(i,group)| format!("{}: {}",i,group)).collect();
+    Err(ViewError::Parse(format!("{} holds {} dicom series, pick one of\n{}",path.display(),groups.len(),groups.join("\n"))))
+}
+
+/// reads the images of a directory, or a single dicom file, grouped into the arrays they stack into. Files
+/// in the directory that aren't dicom images, such as a DICOMDIR, or that can't be read, such as compressed
+/// images, are skipped. If no images are left, the error of the first unreadable file is returned
+pub fn read_dicom_groups(path:impl AsRef<Path>) -> Result<Vec<DicomGroup>,ViewError> {
+    let path = path.as_ref();
+    if !path.is_dir() {
+        return Ok(vec![DicomGroup { images: vec![read_dicom_image(path)?] }])
+    }
+    let mut paths = std::fs::read_dir(path)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<PathBuf>,_>>()?;
+    paths.retain(|path| path.is_file());
+    paths.sort();
+    let results:Vec<Result<Option<DicomImage>,ViewError>> = paths.par_iter()
+        .map(|path|{
+            let mut bytes = vec![];
+            File::open(path)?.read_to_end(&mut bytes)?;
+            parse_dicom(&bytes)
+        })
+        .collect();
+    let mut first_error = None;
+    let images:Vec<DicomImage> = results.into_iter().filter_map(|result| match result {
+        Ok(image) => image,
+        Err(e) => {
+            first_error.get_or_insert(e);
+            None
+        }
+    }).collect();
+    if images.is_empty() {
+        return Err(first_error.unwrap_or(ViewError::Parse(format!("no dicom images in {}",path.display()))))
+    }
+    Ok(group_dicom(images))
+}
+
+/// groups images by series, size and orientation, in the order the groups are first seen. Magnitude and phase
+/// are often saved as separate series, so each phase-only group is merged into the first magnitude-only group
+/// with the same size, orientation, slice positions and echoes
+pub fn group_dicom(images:Vec<DicomImage>) -> Vec<DicomGroup> {
+    let mut groups:Vec<DicomGroup> = vec![];
+    for image in images {
+        match groups.iter_mut().find(|group| group.images[0].stacks_with(&image)) {
+            Some(group) => group.images.push(image),
+            None => groups.push(DicomGroup { images: vec![image] }),
+        }
+    }
+    let (phase,mut groups):(Vec<DicomGroup>,Vec<DicomGroup>) = groups.into_iter()
+        .partition(|group| group.images.iter().all(|image| image.phase));
+    for group in phase {
+        match groups.iter_mut().find(|other| other.pairs_with(&group)) {
+            Some(other) => other.images.extend(group.images),
+            None => groups.push(group),
+        }
+    }
+    groups
+}
+
+/// reads a single dicom image
+pub fn read_dicom_image(path:impl AsRef<Path>) -> Result<DicomImage,ViewError> {
+    let path = path.as_ref();
+    let mut bytes = vec![];
+    File::open(path)?.read_to_end(&mut bytes)?;
+    parse_dicom(&bytes)?.ok_or(ViewError::Parse(format!("{} is not a dicom image",path.display())))
+}
+
+/// stacks images of the same size by slice position along SLICE_DIM, by echo time along ECHO_DIM, and in
+/// order of temporal position and instance number along TIME_DIM. Slice positions are measured along the
+/// normal of the first image. Magnitude and phase images at the same place are combined into complex samples,
+/// and places without an image are left zero
+pub fn stack_dicom(images:&[DicomImage]) -> Result<(CflBuffer,DicomInfo),ViewError> {
+    let mut images:Vec<&DicomImage> = images.iter().collect();
+    images.sort_by_key(|image| (image.temporal_position,image.instance_number));
+    let first = *images.first().ok_or(ViewError::Parse("no dicom images".to_string()))?;
+    let (rows,columns) = (first.rows,first.columns);
+    if rows == 0 || columns == 0 {
+        return Err(ViewError::Parse(format!("can't stack {}x{} dicom images",columns,rows)))
+    }
+    if let Some(image) = images.iter().find(|image| (image.rows,image.columns) != (rows,columns)) {
+        return Err(ViewError::Parse(format!(
+            "can't stack {}x{} and {}x{} dicom images",columns,rows,image.columns,image.rows
+        )))
+    }
+    let normal = first.orientation.map(|orientation| normal(&orientation));
+    let slices = distinct(images.iter().map(|image| image.slice_position(normal)));
+    let echoes = distinct(images.iter().map(|image| image.echo()));
+
+    // the place of each image, counting the images already placed at the same slice and echo for the time index
+    let mut counts:HashMap<(usize,usize,bool),usize> = HashMap::new();
+    let places:Vec<[usize;3]> = images.iter().map(|image|{
+        let slice = nearest(&slices,image.slice_position(normal));
+        let echo = nearest(&echoes,image.echo());
+        let count = counts.entry((slice,echo,image.phase)).or_default();
+        *count += 1;
+        [slice,echo,*count - 1]
+    }).collect();
+    let n_times = counts.values().copied().max().unwrap_or(1);
+
+    let mut shape = [1;N_DIMS];
+    shape[0] = columns;
+    shape[1] = rows;
+    shape[SLICE_DIM] = slices.len();
+    shape[ECHO_DIM] = echoes.len();
+    shape[TIME_DIM] = n_times;
+    let dims = ArrayDim::from_shape(&shape);
+    let plane_size = rows * columns;
+    let n_planes = dims.numel() / plane_size;
+    let mut magnitude:Vec<Option<&[f32]>> = vec![None;n_planes];
+    let mut phase:Vec<Option<&[f32]>> = vec![None;n_planes];
+    for (image,place) in images.iter().zip(&places) {
+        let mut idx = [0;N_DIMS];
+        idx[SLICE_DIM] = place[0];
+        idx[ECHO_DIM] = place[1];
+        idx[TIME_DIM] = place[2];
+        let plane = dims.calc_addr(&idx) / plane_size;
+        let pixels = image.pixels.get(..plane_size).ok_or(ViewError::BufferSize { needed: plane_size, got: image.pixels.len() })?;
+        if image.phase {
+            phase[plane] = Some(pixels);
+        }else {
+            magnitude[plane] = Some(pixels);
+        }
+    }
+    let data = magnitude.iter().zip(&phase).flat_map(|(magnitude,phase)|{
+        (0..plane_size).map(move |i| match (magnitude,phase) {
+            (Some(magnitude),Some(phase)) => Complex32::from_polar(magnitude[i],phase[i]),
+            (Some(magnitude),None) => Complex32::new(magnitude[i],0.),
+            (None,Some(phase)) => Complex32::from_polar(1.,phase[i]),
+            (None,None) => Complex32::ZERO,
+        })
+    }).collect();
+
+    let spacing = first.pixel_spacing.unwrap_or([1.,1.]);
+    let slice_spacing = match slices.len() {
+        1 => first.slice_thickness.unwrap_or(1.),
+        n => (slices[n - 1] - slices[0]) / (n - 1) as f32,
+    };
+    let voxel_size = [spacing[1],spacing[0],slice_spacing];
+    let mut cfl_buffer = CflBuffer::new(data,dims);
+    cfl_buffer.voxel_size[..3].copy_from_slice(&voxel_size);
+    cfl_buffer.geometry_known = true;
+    let origin = images.iter().zip(&places).find(|(_,place)| place[0] == 0).and_then(|(image,_)| image.position);
+    cfl_buffer.affine = first.orientation.zip(normal).zip(origin).map(|((orientation,normal),origin)|{
+        // patient coordinates are LPS, while the affine follows nifti and is RAS
+        let flip = [-1.,-1.,1.];
+        std::array::from_fn(|k| [
+            flip[k] * orientation[k] * voxel_size[0],
+            flip[k] * orientation[k + 3] * voxel_size[1],
+            flip[k] * normal[k] * voxel_size[2],
+            flip[k] * origin[k],
+        ])
+    });
+
+    let mut series_descriptions:Vec<String> = vec![];
+    for image in &images {
+        if !series_descriptions.contains(&image.series_description) {
+            series_descriptions.push(image.series_description.clone());
+        }
+    }
+    let info = DicomInfo {
+        series_descriptions,
+        voxel_size,
+        slice_thickness: first.slice_thickness,
+        echo_times: if images.iter().all(|image| image.echo_time.is_some()) { echoes } else { vec![] },
+        repetition_time: first.repetition_time,
+        n_images: images.len(),
+        magnitude: images.iter().any(|image| !image.phase),
+        phase: images.iter().any(|image| image.phase),
+    };
+    Ok((cfl_buffer,info))
+}
+
+/// parses the contents of a dicom file, or returns None for files that aren't dicom or hold no image
+fn parse_dicom(bytes:&[u8]) -> Result<Option<DicomImage>,ViewError> {
+    if bytes.get(PREAMBLE_LEN..PREAMBLE_LEN + DICOM_MAGIC.len()) != Some(DICOM_MAGIC) {
+        return Ok(None)
+    }
+    let inflated;
+    let mut elements = HashMap::new();
+    // the file meta group is always explicit little endian
+    let mut meta = Reader { bytes, pos: PREAMBLE_LEN + DICOM_MAGIC.len(), explicit: true, big_endian: false };
+    while bytes.get(meta.pos..meta.pos + 2) == Some(&[0x02,0x00]) {
+        let (tag,len) = meta.header()?;
+        elements.insert(tag,meta.take(len as usize)?);
+    }
+    let rest = &bytes[meta.pos..];
+    let syntax = text(&elements,TRANSFER_SYNTAX).unwrap_or(EXPLICIT_LITTLE.to_string());
+    let (data,explicit,big_endian) = match syntax.as_str() {
+        IMPLICIT_LITTLE => (rest,false,false),
+        EXPLICIT_LITTLE => (rest,true,false),
+        EXPLICIT_BIG => (rest,true,true),
+        DEFLATED_LITTLE => {
+            let mut decoded = vec![];
+            DeflateDecoder::new(rest).read_to_end(&mut decoded)?;
+            inflated = decoded;
+            (inflated.as_slice(),true,false)
+        }
+        _ => return Err(ViewError::Parse(format!("unsupported dicom transfer syntax {}, only uncompressed images are read",syntax))),
+    };
+    let mut reader = Reader { bytes: data, pos: 0, explicit, big_endian };
+    while reader.pos < data.len() {
+        let (tag,len) = reader.header()?;
+        if len == UNDEFINED_LENGTH {
+            if tag == PIXEL_DATA {
+                return Err(ViewError::Parse("encapsulated dicom pixel data isn't supported".to_string()))
+            }
+            reader.skip_until(SEQUENCE_END)?;
+        }else {
+            elements.insert(tag,reader.take(len as usize)?);
+        }
+    }
+    image(&elements,big_endian)
+}
+
+/// the image described by the top level elements of a data set
+fn image(elements:&HashMap<Tag,&[u8]>, big_endian:bool) -> Result<Option<DicomImage>,ViewError> {
+    let Some(pixel_data) = elements.get(&PIXEL_DATA) else {
+        return Ok(None)
+    };
+    let unsigned = |tag| elements.get(&tag).filter(|value| value.len() >= 2).map(|value| u16::from_le_bytes(le(value,big_endian)) as usize);
+    let rows = unsigned(ROWS).filter(|&rows| rows > 0).ok_or(ViewError::Parse("dicom image without rows".to_string()))?;
+    let columns = unsigned(COLUMNS).filter(|&columns| columns > 0).ok_or(ViewError::Parse("dicom image without columns".to_string()))?;
+    if unsigned(SAMPLES_PER_PIXEL).unwrap_or(1) != 1 {
+        return Err(ViewError::Parse("color dicom images aren't supported".to_string()))
+    }
+    if number(elements,NUMBER_OF_FRAMES).unwrap_or(1.) > 1. {
+        return Err(ViewError::Parse("multi-frame dicom images aren't supported".to_string()))
+    }
+    let bits_allocated = unsigned(BITS_ALLOCATED).unwrap_or(16);
+    let bits_stored = unsigned(BITS_STORED).unwrap_or(bits_allocated).clamp(1,bits_allocated.max(1));
+    let signed = unsigned(PIXEL_REPRESENTATION) == Some(1);
+    let dtype = match (bits_allocated,signed) {
+        (8,false) => RawType::U8,
+        (8,true) => RawType::I8,
+        (16,false) => RawType::U16,
+        (16,true) => RawType::I16,
+        (32,false) => RawType::U32,
+        (32,true) => RawType::I32,
+        _ => return Err(ViewError::Parse(format!("unsupported dicom bits allocated {}",bits_allocated))),
+    };
+    let needed = rows * columns * dtype.size();
+    let pixel_data = pixel_data.get(..needed).ok_or(ViewError::BufferSize { needed, got: pixel_data.len() })?;
+    let endianness = if big_endian { Endianness::Big } else { Endianness::Little };
+    let slope = number(elements,RESCALE_SLOPE).unwrap_or(1.);
+    let intercept = number(elements,RESCALE_INTERCEPT).unwrap_or(0.);
+    let mut pixels:Vec<f32> = dtype.decode(pixel_data,endianness).into_iter().map(|x| x * slope + intercept).collect();
+
+    let image_type = text(elements,IMAGE_TYPE).unwrap_or_default();
+    let phase = image_type.split('\\').any(|value| value == "P" || value == "PHASE");
+    if phase {
+        // the stored values span a full turn, centered on zero phase
+        let half_turn = slope * (1u64 << (bits_stored - 1)) as f32;
+        let zero = intercept + if signed { 0. } else { half_turn };
+        pixels.iter_mut().for_each(|x| *x = (*x - zero) / half_turn * PI);
+    }
+
+    Ok(Some(DicomImage {
+        rows,
+        columns,
+        pixels,
+        phase,
+        series_uid: text(elements,SERIES_UID).unwrap_or_default(),
+        series_description: text(elements,SERIES_DESCRIPTION).unwrap_or_default(),
+        pixel_spacing: numbers(elements,PIXEL_SPACING).try_into().ok(),
+        slice_thickness: number(elements,SLICE_THICKNESS),
+        repetition_time: number(elements,REPETITION_TIME),
+        echo_time: number(elements,ECHO_TIME),
+        echo_number: number(elements,ECHO_NUMBER).map(|n| n as i32),
+        instance_number: number(elements,INSTANCE_NUMBER).map(|n| n as i32),
+        temporal_position: number(elements,TEMPORAL_POSITION).map(|n| n as i32),
+        position: numbers(elements,IMAGE_POSITION).try_into().ok(),
+        orientation: numbers(elements,IMAGE_ORIENTATION).try_into().ok(),
+        slice_location: number(elements,SLICE_LOCATION),
+    }))
+}
+
+/// walks the data elements of a data set
+struct Reader<'a> {
+    bytes:&'a [u8],
+    pos:usize,
+    /// whether elements carry their value representation
+    explicit:bool,
+    big_endian:bool,
+}
+
+impl<'a> Reader<'a> {
+
+    fn take(&mut self, n:usize) -> Result<&'a [u8],ViewError> {
+        let end = self.pos + n;
+        let bytes = self.bytes.get(self.pos..end).ok_or(ViewError::BufferSize { needed: end, got: self.bytes.len() })?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> Result<u16,ViewError> {
+        Ok(u16::from_le_bytes(le(self.take(2)?,self.big_endian)))
+    }
+
+    fn u32(&mut self) -> Result<u32,ViewError> {
+        Ok(u32::from_le_bytes(le(self.take(4)?,self.big_endian)))
+    }
+
+    /// the tag and value length of the next element, leaving the reader at its value
+    fn header(&mut self) -> Result<(Tag,u32),ViewError> {
+        let tag = (self.u16()?,self.u16()?);
+        // items and delimiters have no value representation
+        if !self.explicit || tag.0 == 0xFFFE {
+            return Ok((tag,self.u32()?))
+        }
+        let vr = self.take(2)?;
+        if matches!(vr,b"OB" | b"OD" | b"OF" | b"OL" | b"OV" | b"OW" | b"SQ" | b"SV" | b"UC" | b"UN" | b"UR" | b"UT" | b"UV") {
+            self.take(2)?;
+            Ok((tag,self.u32()?))
+        }else {
+            Ok((tag,self.u16()? as u32))
+        }
+    }
+
+    /// skips elements up to and including the delimiter `end`, along with any nested sequences and items of
+    /// undefined length
+    fn skip_until(&mut self, end:Tag) -> Result<(),ViewError> {
+        loop {
+            let (tag,len) = self.header()?;
+            if tag == end {
+                return Ok(())
+            }
+            if len == UNDEFINED_LENGTH {
+                self.skip_until(if tag == ITEM { ITEM_END } else { SEQUENCE_END })?;
+            }else {
+                self.take(len as usize)?;
+            }
+        }
+    }
+
+}
+
+/// the text of an element without its padding
+fn text(elements:&HashMap<Tag,&[u8]>, tag:Tag) -> Option<String> {
+    elements.get(&tag).map(|value| String::from_utf8_lossy(value).trim_matches(|c:char| c == '\0' || c.is_whitespace()).to_string())
+}
+
+/// the backslash separated values of a decimal or integer string
+fn numbers(elements:&HashMap<Tag,&[u8]>, tag:Tag) -> Vec<f32> {
+    text(elements,tag).map(|text| text.split('\\').filter_map(|value| value.trim().parse().ok()).collect()).unwrap_or_default()
+}
+
+fn number(elements:&HashMap<Tag,&[u8]>, tag:Tag) -> Option<f32> {
+    numbers(elements,tag).first().copied()
+}
+
+/// how a group's images are described
+fn kind(magnitude:bool, phase:bool) -> &'static str {
+    match (magnitude,phase) {
+        (true,true) => "magnitude and phase",
+        (false,true) => "phase",
+        _ => "magnitude",
+    }
+}
+
+/// true if both images have no orientation, or direction cosines that agree
+fn same_orientation(a:Option<[f32;6]>, b:Option<[f32;6]>) -> bool {
+    match (a,b) {
+        (Some(a),Some(b)) => a.iter().zip(b).all(|(a,b)| (a - b).abs() < 1e-3),
+        (a,b) => a.is_none() && b.is_none(),
+    }
+}
+
+/// the normal of the image plane from the row and column direction cosines
+fn normal(orientation:&[f32;6]) -> [f32;3] {
+    let (r,c) = (&orientation[..3],&orientation[3..]);
+    [r[1] * c[2] - r[2] * c[1], r[2] * c[0] - r[0] * c[2], r[0] * c[1] - r[1] * c[0]]
+}
+
+/// the values in ascending order, merging values closer than TOLERANCE
+fn distinct(values:impl Iterator<Item=f32>) -> Vec<f32> {
+    let mut values:Vec<f32> = values.collect();
+    values.sort_by(f32::total_cmp);
+    values.dedup_by(|a,b| (*a - *b).abs() < TOLERANCE);
+    values
+}
+
+/// index of the value closest to x
+fn nearest(values:&[f32], x:f32) -> usize {
+    (0..values.len()).min_by(|&a,&b| (values[a] - x).abs().total_cmp(&(values[b] - x).abs())).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AXIAL:[f32;6] = [1.,0.,0.,0.,1.,0.];
+
+    /// a 3x2 image at height z whose pixels count up from `first`
+    fn image(z:f32, echo_time:f32, instance_number:i32, phase:bool, first:f32) -> DicomImage {
+        DicomImage {
+            rows: 2,
+            columns: 3,
+            pixels: (0..6).map(|i| first + i as f32).collect(),
+            phase,
+            series_uid: if phase { "1.2.2" } else { "1.2.1" }.to_string(),
+            pixel_spacing: Some([0.5,0.25]),
+            echo_time: Some(echo_time),
+            instance_number: Some(instance_number),
+            position: Some([-10.,-20.,z]),
+            orientation: Some(AXIAL),
+            ..DicomImage::default()
+        }
+    }
+
+    /// a dicom file with an explicit little endian meta group naming the transfer syntax, then a 2x1 image of
+    /// 16-bit pixels 1 and 2 in implicit little endian
+    fn dicom_file(syntax:&str) -> Vec<u8> {
+        let mut bytes = vec![0;PREAMBLE_LEN];
+        bytes.extend(DICOM_MAGIC);
+        let mut syntax = syntax.as_bytes().to_vec();
+        if syntax.len() % 2 == 1 {
+            syntax.push(0);
+        }
+        bytes.extend([0x02,0x00,0x10,0x00]);
+        bytes.extend(b"UI");
+        bytes.extend((syntax.len() as u16).to_le_bytes());
+        bytes.extend(syntax);
+        for (tag,value) in [(ROWS,vec![1,0]),(COLUMNS,vec![2,0]),(PIXEL_DATA,vec![1,0,2,0])] {
+            bytes.extend(tag.0.to_le_bytes());
+            bytes.extend(tag.1.to_le_bytes());
+            bytes.extend((value.len() as u32).to_le_bytes());
+            bytes.extend(value);
+        }
+        bytes
+    }
+
+    #[test]
+    fn skips_unreadable_files() {
+        let dir = std::env::temp_dir().join(format!("cfl_view_dicom_{}",std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("jpeg.dcm"),dicom_file("1.2.840.10008.1.2.4.50")).unwrap();
+        // only the compressed image, so its error is returned
+        assert!(matches!(read_dicom_groups(&dir),Err(ViewError::Parse(e)) if e.contains("transfer syntax")));
+        std::fs::write(dir.join("image.dcm"),dicom_file(IMPLICIT_LITTLE)).unwrap();
+        std::fs::write(dir.join("notes.txt"),"not dicom").unwrap();
+        let groups = read_dicom_groups(&dir).unwrap();
+        assert_eq!(groups.len(),1);
+        assert_eq!(groups[0].images.len(),1);
+        assert_eq!((groups[0].images[0].rows,groups[0].images[0].columns),(1,2));
+        assert_eq!(groups[0].images[0].pixels,[1.,2.]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn stack_places_slices_echoes_and_times() {
+        let mut images = vec![];
+        for (t,instance) in [(1,100),(0,0)] {
+            for (s,z) in [(1,5.),(0,3.)] {
+                for (e,te) in [(0,5.),(1,10.)] {
+                    let first = (100 * t + 10 * s + e) as f32;
+                    images.push(image(z,te,instance + 10 * s + e,false,first));
+                }
+            }
+        }
+        let (cfl_buffer,info) = stack_dicom(&images).unwrap();
+        let shape = cfl_buffer.dims.shape();
+        assert_eq!((shape[0],shape[1],shape[SLICE_DIM],shape[ECHO_DIM],shape[TIME_DIM]),(3,2,2,2,2));
+        for (t,s,e) in [(0,0,0),(1,1,0),(0,1,1),(1,0,1)] {
+            let mut idx = [0;N_DIMS];
+            idx[0] = 2;
+            idx[1] = 1;
+            idx[SLICE_DIM] = s;
+            idx[ECHO_DIM] = e;
+            idx[TIME_DIM] = t;
+            let first = (100 * t + 10 * s + e) as f32;
+            assert_eq!(cfl_buffer.get(&idx),Some(Complex32::new(first + 5.,0.)));
+        }
+        assert_eq!(&cfl_buffer.voxel_size[..3],&[0.25,0.5,2.]);
+        assert_eq!(cfl_buffer.affine.unwrap()[2],[0.,0.,2.,3.]);
+        assert_eq!(info.echo_times,vec![5.,10.]);
+        assert_eq!(info.n_images,8);
+    }
+
+    #[test]
+    fn pairs_magnitude_and_phase() {
+        let mut phase = image(3.,5.,1,true,0.);
+        phase.pixels = vec![0.,PI / 2.,PI,0.,0.,0.];
+        let images = vec![image(3.,5.,0,false,1.),phase,image(5.,5.,2,false,1.),image(5.,5.,3,true,0.)];
+        let groups = group_dicom(images);
+        assert_eq!(groups.len(),1);
+        let (cfl_buffer,info) = groups[0].stack().unwrap();
+        assert!(info.magnitude && info.phase);
+        let expected = [Complex32::new(1.,0.),Complex32::new(0.,2.),Complex32::new(-3.,0.)];
+        for (x,expected) in cfl_buffer.data.iter().zip(expected) {
+            assert!((x - expected).norm() < 1e-5,"{} {}",x,expected);
+        }
+        assert_eq!(cfl_buffer.data[6],Complex32::new(1.,0.));
+    }
+
+    #[test]
+    fn groups_by_series_and_orientation() {
+        let mut sagittal = image(0.,5.,3,false,0.);
+        sagittal.orientation = Some([0.,1.,0.,0.,0.,-1.]);
+        // phase of another protocol, at slices the magnitude doesn't have
+        let other_phase = image(7.,5.,4,true,0.);
+        let groups = group_dicom(vec![image(3.,5.,0,false,0.),sagittal,other_phase,image(5.,5.,1,false,0.)]);
+        let sizes:Vec<usize> = groups.iter().map(|group| group.images.len()).collect();
+        assert_eq!(sizes,vec![2,1,1]);
+        assert!(groups[2].images[0].phase);
+    }
+
+    #[test]
+    fn phase_rescaled_to_radians() {
+        let pixels:Vec<u8> = [0u16,2048,3072,4095,0,0].iter().flat_map(|x| x.to_le_bytes()).collect();
+        let (rows,columns,bits_allocated,bits_stored) = (2u16.to_le_bytes(),3u16.to_le_bytes(),16u16.to_le_bytes(),12u16.to_le_bytes());
+        let mut elements:HashMap<Tag,&[u8]> = HashMap::from([
+            (IMAGE_TYPE,b"ORIGINAL\\PRIMARY\\P\\ND ".as_slice()),
+            (ROWS,rows.as_slice()),
+            (COLUMNS,columns.as_slice()),
+            (BITS_ALLOCATED,bits_allocated.as_slice()),
+            (BITS_STORED,bits_stored.as_slice()),
+            (RESCALE_SLOPE,b"2 ".as_slice()),
+            (RESCALE_INTERCEPT,b"-4096 ".as_slice()),
+            (PIXEL_DATA,pixels.as_slice()),
+        ]);
+        let phase = super::image(&elements,false).unwrap().unwrap();
+        assert!(phase.phase);
+        for (x,expected) in phase.pixels.iter().zip([-PI,0.,PI / 2.,PI * 4094. / 4096.]) {
+            assert!((x - expected).abs() < 1e-5,"{} {}",x,expected);
+        }
+
+        let zero = 0u16.to_le_bytes();
+        elements.insert(ROWS,zero.as_slice());
+        assert!(super::image(&elements,false).is_err());
+    }
+}
@@ -0,0 +1,97 @@
+use std::fmt::{Display, Formatter};
+use array_lib::cfl::num_complex::Complex32;
+use rayon::prelude::*;
+use crate::ViewError;
+
+/// how two arrays of the same size are combined into a difference image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DifferenceMode {
+    /// complex difference A - B
+    Difference,
+    /// difference of the magnitudes |A| - |B|, stored as a real value
+    MagnitudeDifference,
+    /// complex ratio A / B, set to zero where B is zero
+    Ratio,
+    /// phase difference angle(A conj(B)) in radians, stored as a real value
+    PhaseDifference,
+}
+
+impl DifferenceMode {
+    pub const ALL: [DifferenceMode;4] = [
+        DifferenceMode::Difference,
+        DifferenceMode::MagnitudeDifference,
+        DifferenceMode::Ratio,
+        DifferenceMode::PhaseDifference,
+    ];
+
+    pub fn combine(&self, a:Complex32, b:Complex32) -> Complex32 {
+        match self {
+            DifferenceMode::Difference => a - b,
+            DifferenceMode::MagnitudeDifference => Complex32::new(a.norm() - b.norm(),0.),
+            DifferenceMode::Ratio => if b == Complex32::ZERO { Complex32::ZERO } else { a / b },
+            DifferenceMode::PhaseDifference => Complex32::new((a * b.conj()).arg(),0.),
+        }
+    }
+
+    /// the value of identical inputs, which the signed colormap is centered on
+    pub fn center(&self) -> f32 {
+        match self {
+            DifferenceMode::Ratio => 1.,
+            _ => 0.,
+        }
+    }
+
+    /// combines two arrays sample by sample
+    pub fn apply(&self, a:&[Complex32], b:&[Complex32]) -> Result<Vec<Complex32>,ViewError> {
+        if a.len() != b.len() {
+            return Err(ViewError::BufferSize { needed: a.len(), got: b.len() });
+        }
+        Ok(a.par_iter().zip(b.par_iter()).map(|(&a,&b)| self.combine(a,b)).collect())
+    }
+}
+
+impl Display for DifferenceMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DifferenceMode::Difference => write!(f, "A - B"),
+            DifferenceMode::MagnitudeDifference => write!(f, "|A| - |B|"),
+            DifferenceMode::Ratio => write!(f, "A / B"),
+            DifferenceMode::PhaseDifference => write!(f, "∠(A B*)"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    fn c(re:f32, im:f32) -> Complex32 {
+        Complex32::new(re,im)
+    }
+
+    #[test]
+    fn each_mode() {
+        let a = [c(3.,4.),c(0.,2.),c(1.,0.)];
+        let b = [c(1.,0.),c(0.,1.),c(0.,0.)];
+        assert_eq!(DifferenceMode::Difference.apply(&a,&b).unwrap(),[c(2.,4.),c(0.,1.),c(1.,0.)]);
+        assert_eq!(DifferenceMode::MagnitudeDifference.apply(&a,&b).unwrap(),[c(4.,0.),c(1.,0.),c(1.,0.)]);
+        assert_eq!(DifferenceMode::Ratio.apply(&a,&b).unwrap(),[c(3.,4.),c(2.,0.),c(0.,0.)]);
+        let phase = DifferenceMode::PhaseDifference.apply(&a,&b).unwrap();
+        assert!((phase[0].re - (4f32).atan2(3.)).abs() < 1e-6);
+        assert_eq!(phase[1],c(0.,0.));
+        assert!((DifferenceMode::PhaseDifference.combine(c(0.,1.),c(1.,0.)).re - FRAC_PI_2).abs() < 1e-6);
+        assert!((DifferenceMode::PhaseDifference.combine(c(1.,0.),c(0.,1.)).re + FRAC_PI_2).abs() < 1e-6);
+    }
+
+    #[test]
+    fn identical_inputs_give_the_center() {
+        let a = [c(3.,4.),c(-1.,0.5)];
+        for mode in DifferenceMode::ALL {
+            for x in mode.apply(&a,&a).unwrap() {
+                assert!((x - c(mode.center(),0.)).norm() < 1e-6,"{}",mode);
+            }
+        }
+        assert!(matches!(DifferenceMode::Difference.apply(&a,&a[..1]),Err(ViewError::BufferSize { needed: 2, got: 1 })));
+    }
+}
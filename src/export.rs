@@ -0,0 +1,307 @@
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use tiff::encoder::{colortype, TiffEncoder};
+use crate::ViewError;
+
+/// file type written when exporting a slice
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// the slice as rendered, with colormap, window and annotations
+    Png,
+    /// the displayed values scaled by the window to the full 16-bit range, so values outside the window clip
+    Tiff16,
+    /// the displayed values before windowing
+    TiffFloat,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat;3] = [ExportFormat::Png, ExportFormat::Tiff16, ExportFormat::TiffFloat];
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Png => "png",
+            ExportFormat::Tiff16 | ExportFormat::TiffFloat => "tif",
+        }
+    }
+}
+
+impl Display for ExportFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExportFormat::Png => write!(f, "png"),
+            ExportFormat::Tiff16 => write!(f, "windowed 16-bit tiff"),
+            ExportFormat::TiffFloat => write!(f, "float tiff"),
+        }
+    }
+}
+
+/// file type written when exporting cine playback
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnimationFormat {
+    Gif,
+    /// animated png
+    Apng,
+    /// one numbered png per frame
+    PngSequence,
+}
+
+impl AnimationFormat {
+    pub const ALL: [AnimationFormat;3] = [AnimationFormat::Gif, AnimationFormat::Apng, AnimationFormat::PngSequence];
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            AnimationFormat::Gif => "gif",
+            AnimationFormat::Apng | AnimationFormat::PngSequence => "png",
+        }
+    }
+
+    /// writes rgba frames of the same size, played back at fps frames per second
+    pub fn write(&self, path:&Path, frames:&[Vec<u8>], width:usize, height:usize, fps:f32) -> Result<(),ViewError> {
+        match self {
+            AnimationFormat::Gif => write_gif(path,frames,width,height,fps),
+            AnimationFormat::Apng => write_apng(path,frames,width,height,fps),
+            AnimationFormat::PngSequence => {
+                for (i,frame) in frames.iter().enumerate() {
+                    write_png(numbered_path(path,i,frames.len()),frame,width,height)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Display for AnimationFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AnimationFormat::Gif => write!(f, "gif"),
+            AnimationFormat::Apng => write!(f, "apng"),
+            AnimationFormat::PngSequence => write!(f, "png sequence"),
+        }
+    }
+}
+
+/// writes rgba frames as a looping gif. Colors are quantized to a palette per frame
+pub fn write_gif(path:impl AsRef<Path>, frames:&[Vec<u8>], width:usize, height:usize, fps:f32) -> Result<(),ViewError> {
+    let (Ok(w),Ok(h)) = (u16::try_from(width),u16::try_from(height)) else {
+        return Err(ViewError::Encode(format!("{}x{} is too large for a gif",width,height)));
+    };
+    let writer = BufWriter::new(File::create(path)?);
+    let mut encoder = gif::Encoder::new(writer,w,h,&[]).map_err(encode_error)?;
+    encoder.set_repeat(gif::Repeat::Infinite).map_err(encode_error)?;
+    // gif delays are in hundredths of a second
+    let delay = (100. / fps.max(0.1)).round() as u16;
+    for rgba in frames {
+        check_size(rgba.len(),width * height * 4)?;
+        let mut rgba = rgba.clone();
+        let mut frame = gif::Frame::from_rgba_speed(w,h,&mut rgba,10);
+        frame.delay = delay;
+        encoder.write_frame(&frame).map_err(encode_error)?;
+    }
+    Ok(())
+}
+
+/// writes rgba frames as a looping animated png
+pub fn write_apng(path:impl AsRef<Path>, frames:&[Vec<u8>], width:usize, height:usize, fps:f32) -> Result<(),ViewError> {
+    let writer = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(writer,width as u32,height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_animated(frames.len() as u32,0).map_err(encode_error)?;
+    // delays are a fraction of a second, given here in milliseconds
+    encoder.set_frame_delay((1000. / fps.max(0.1)).round() as u16,1000).map_err(encode_error)?;
+    let mut writer = encoder.write_header().map_err(encode_error)?;
+    for rgba in frames {
+        check_size(rgba.len(),width * height * 4)?;
+        writer.write_image_data(rgba).map_err(encode_error)?;
+    }
+    writer.finish().map_err(encode_error)
+}
+
+/// writes 8-bit rgba bytes as a png
+pub fn write_png(path:impl AsRef<Path>, rgba:&[u8], width:usize, height:usize) -> Result<(),ViewError> {
+    check_size(rgba.len(),width * height * 4)?;
+    let writer = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(writer,width as u32,height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(encode_error)?;
+    writer.write_image_data(rgba).map_err(encode_error)?;
+    writer.finish().map_err(encode_error)
+}
+
+/// writes a single channel 16-bit tiff
+pub fn write_tiff_u16(path:impl AsRef<Path>, values:&[u16], width:usize, height:usize) -> Result<(),ViewError> {
+    check_size(values.len(),width * height)?;
+    let mut encoder = TiffEncoder::new(BufWriter::new(File::create(path)?)).map_err(encode_error)?;
+    encoder.write_image::<colortype::Gray16>(width as u32,height as u32,values).map_err(encode_error)
+}
+
+/// writes a single channel 32-bit float tiff
+pub fn write_tiff_f32(path:impl AsRef<Path>, values:&[f32], width:usize, height:usize) -> Result<(),ViewError> {
+    check_size(values.len(),width * height)?;
+    let mut encoder = TiffEncoder::new(BufWriter::new(File::create(path)?)).map_err(encode_error)?;
+    encoder.write_image::<colortype::Gray32Float>(width as u32,height as u32,values).map_err(encode_error)
+}
+
+/// path for the i-th of n files, e.g. slices_007.png. The index is zero padded to the width of n - 1
+pub fn numbered_path(path:&Path, i:usize, n:usize) -> PathBuf {
+    let digits = n.saturating_sub(1).max(1).ilog10() as usize + 1;
+    let stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let name = match path.extension() {
+        Some(ext) => format!("{}_{:0digits$}.{}",stem,i,ext.to_string_lossy()),
+        None => format!("{}_{:0digits$}",stem,i),
+    };
+    path.with_file_name(name)
+}
+
+/// draws a one pixel wide line into rgba bytes. Points are in pixel coordinates where pixel (x,y) covers
+/// [x,x+1) x [y,y+1), the same as region outlines
+pub fn draw_line(rgba:&mut [u8], width:usize, height:usize, from:[f32;2], to:[f32;2], color:[u8;4]) {
+    let dx = to[0] - from[0];
+    let dy = to[1] - from[1];
+    let n = dx.abs().max(dy.abs()).ceil() as usize + 1;
+    for i in 0..n {
+        let t = if n > 1 { i as f32 / (n - 1) as f32 } else { 0. };
+        let x = (from[0] + t * dx).floor();
+        let y = (from[1] + t * dy).floor();
+        if x >= 0. && y >= 0. && (x as usize) < width && (y as usize) < height {
+            let p = (y as usize * width + x as usize) * 4;
+            if let Some(pixel) = rgba.get_mut(p..p + 4) {
+                pixel.copy_from_slice(&color);
+            }
+        }
+    }
+}
+
+/// draws the outline of a closed polygon into rgba bytes
+pub fn draw_polygon(rgba:&mut [u8], width:usize, height:usize, points:&[[f32;2]], color:[u8;4]) {
+    for (i,&from) in points.iter().enumerate() {
+        let to = points[(i + 1) % points.len()];
+        draw_line(rgba,width,height,from,to,color);
+    }
+}
+
+fn check_size(got:usize, needed:usize) -> Result<(),ViewError> {
+    if got < needed {
+        return Err(ViewError::BufferSize { needed, got });
+    }
+    Ok(())
+}
+
+fn encode_error(e:impl Display) -> ViewError {
+    ViewError::Encode(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::BufReader;
+    use tiff::decoder::{Decoder, DecodingResult};
+
+    fn temp_path(name:&str) -> PathBuf {
+        std::env::temp_dir().join(format!("cfl_view_export_{}_{}",std::process::id(),name))
+    }
+
+    fn read_tiff(path:&Path) -> ((u32,u32),DecodingResult) {
+        let mut decoder = Decoder::new(BufReader::new(File::open(path).unwrap())).unwrap();
+        (decoder.dimensions().unwrap(),decoder.read_image().unwrap())
+    }
+
+    #[test]
+    fn png_bytes() {
+        let path = temp_path("slice.png");
+        let rgba:Vec<u8> = (0..24).collect();
+        write_png(&path,&rgba,3,2).unwrap();
+        let mut reader = png::Decoder::new(BufReader::new(File::open(&path).unwrap())).read_info().unwrap();
+        let mut buf = vec![0;reader.output_buffer_size().unwrap()];
+        let info = reader.next_frame(&mut buf).unwrap();
+        assert_eq!((info.width,info.height,info.color_type),(3,2,png::ColorType::Rgba));
+        assert_eq!(buf,rgba);
+        assert!(matches!(write_png(&path,&rgba[..23],3,2),Err(ViewError::BufferSize { needed: 24, got: 23 })));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn tiff_values() {
+        let path = temp_path("slice.tif");
+        write_tiff_u16(&path,&[0,1,u16::MAX,300,7,8],3,2).unwrap();
+        let (dims,image) = read_tiff(&path);
+        assert_eq!(dims,(3,2));
+        assert!(matches!(image,DecodingResult::U16(values) if values == [0,1,u16::MAX,300,7,8]));
+        write_tiff_f32(&path,&[-1.5,0.,1e-3,2.,f32::MAX,4.],2,3).unwrap();
+        let (dims,image) = read_tiff(&path);
+        assert_eq!(dims,(2,3));
+        assert!(matches!(image,DecodingResult::F32(values) if values == [-1.5,0.,1e-3,2.,f32::MAX,4.]));
+        assert!(matches!(write_tiff_f32(&path,&[0.;5],2,3),Err(ViewError::BufferSize { needed: 6, got: 5 })));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    /// two 2x1 rgba frames of flat colors, which survive gif quantization
+    fn frames() -> Vec<Vec<u8>> {
+        vec![[255,0,0,255,0,255,0,255].to_vec(),[0,0,255,255,255,255,255,255].to_vec()]
+    }
+
+    #[test]
+    fn animations() {
+        let path = temp_path("cine.gif");
+        AnimationFormat::Gif.write(&path,&frames(),2,1,20.).unwrap();
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::RGBA);
+        let mut decoder = options.read_info(BufReader::new(File::open(&path).unwrap())).unwrap();
+        let mut decoded = vec![];
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            assert_eq!(frame.delay,5);
+            decoded.push(frame.buffer.to_vec());
+        }
+        assert_eq!(decoded,frames());
+        std::fs::remove_file(&path).unwrap();
+
+        let path = temp_path("cine.png");
+        AnimationFormat::Apng.write(&path,&frames(),2,1,20.).unwrap();
+        let mut reader = png::Decoder::new(BufReader::new(File::open(&path).unwrap())).read_info().unwrap();
+        assert_eq!(reader.info().animation_control.map(|a| (a.num_frames,a.num_plays)),Some((2,0)));
+        let mut buf = vec![0;reader.output_buffer_size().unwrap()];
+        for frame in frames() {
+            reader.next_frame(&mut buf).unwrap();
+            assert_eq!(reader.info().frame_control.map(|f| (f.delay_num,f.delay_den)),Some((50,1000)));
+            assert_eq!(buf,frame);
+        }
+        std::fs::remove_file(&path).unwrap();
+
+        AnimationFormat::PngSequence.write(&path,&frames(),2,1,20.).unwrap();
+        for (i,frame) in frames().iter().enumerate() {
+            let numbered = numbered_path(&path,i,2);
+            let mut reader = png::Decoder::new(BufReader::new(File::open(&numbered).unwrap())).read_info().unwrap();
+            let mut buf = vec![0;reader.output_buffer_size().unwrap()];
+            reader.next_frame(&mut buf).unwrap();
+            assert_eq!(&buf,frame);
+            std::fs::remove_file(&numbered).unwrap();
+        }
+        assert!(!path.exists());
+        assert!(matches!(AnimationFormat::Gif.write(&path,&[vec![0;7]],2,1,20.),Err(ViewError::BufferSize { needed: 8, got: 7 })));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn numbered_paths() {
+        assert_eq!(numbered_path(Path::new("out/slices.png"),7,12),Path::new("out/slices_07.png"));
+        assert_eq!(numbered_path(Path::new("slices.png"),0,1),Path::new("slices_0.png"));
+        assert_eq!(numbered_path(Path::new("slices"),99,100),Path::new("slices_99"));
+    }
+
+    #[test]
+    fn annotations() {
+        let red = [255,0,0,255];
+        let mut rgba = vec![0;3 * 3 * 4];
+        draw_line(&mut rgba,3,3,[0.5,0.5],[2.5,2.5],red);
+        let drawn:Vec<usize> = (0..9).filter(|p| rgba[p * 4..p * 4 + 4] == red).collect();
+        assert_eq!(drawn,[0,4,8]);
+        // outlines running off the image are clipped
+        let mut rgba = vec![0;3 * 3 * 4];
+        draw_polygon(&mut rgba,3,3,&[[0.5,0.5],[5.,0.5],[0.5,5.]],red);
+        let drawn:Vec<usize> = (0..9).filter(|p| rgba[p * 4..p * 4 + 4] == red).collect();
+        assert_eq!(drawn,[0,1,2,3,6]);
+    }
+}
@@ -0,0 +1,132 @@
+use array_lib::ArrayDim;
+use array_lib::cfl::num_complex::Complex32;
+use rayon::prelude::*;
+use rustfft::{FftDirection, FftPlanner};
+use crate::slice::N_DIMS;
+use crate::ViewError;
+
+/// a centered fft over a set of cfl dimensions, like `bart fft -u`. The zero frequency sits at index n / 2
+/// of each transformed dimension, and the transform is scaled to be unitary so that the inverse undoes it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CenteredFft {
+    /// the cfl dimensions to transform
+    pub axes: [bool;N_DIMS],
+    pub inverse: bool,
+}
+
+impl Default for CenteredFft {
+    /// forward transform over the first two dimensions
+    fn default() -> CenteredFft {
+        let mut axes = [false;N_DIMS];
+        axes[0] = true;
+        axes[1] = true;
+        CenteredFft { axes, inverse: false }
+    }
+}
+
+impl CenteredFft {
+
+    /// transforms an array in place. This works on a full cfl as well as a single extracted slice
+    pub fn apply(&self, data:&mut [Complex32], dims:&ArrayDim) -> Result<(),ViewError> {
+        if data.len() != dims.numel() {
+            return Err(ViewError::BufferSize { needed: dims.numel(), got: data.len() });
+        }
+        let mut planner = FftPlanner::<f32>::new();
+        let direction = if self.inverse { FftDirection::Inverse } else { FftDirection::Forward };
+        for (dim,_) in self.axes.iter().enumerate().filter(|(_,transform)| **transform) {
+            let n = dims.shape()[dim];
+            if n > 1 {
+                let fft = planner.plan_fft(n,direction);
+                fft_axis(data,n,dims.strides()[dim],|line| fft.process(line));
+            }
+        }
+        Ok(())
+    }
+
+}
+
+/// transforms every line of samples along an axis of length n with the given stride
+fn fft_axis(data:&mut [Complex32], n:usize, stride:usize, fft:impl Fn(&mut [Complex32]) + Sync) {
+    let scale = 1. / (n as f32).sqrt();
+    // gather each line into contiguous memory and transform the lines in parallel
+    let mut lines = vec![Complex32::ZERO;data.len()];
+    lines.par_chunks_mut(n).enumerate().for_each(|(l,line)|{
+        let base = (l % stride) + (l / stride) * stride * n;
+        for (k,x) in line.iter_mut().enumerate() {
+            *x = data[base + k * stride];
+        }
+        // ifftshift, transform, then fftshift
+        line.rotate_left(n / 2);
+        fft(line);
+        line.rotate_right(n / 2);
+        line.iter_mut().for_each(|x| *x *= scale);
+    });
+    // scatter the lines back into place
+    data.par_iter_mut().enumerate().for_each(|(i,x)|{
+        let k = (i / stride) % n;
+        let l = (i % stride) + (i / (stride * n)) * stride;
+        *x = lines[l * n + k];
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn along(axes:&[usize], inverse:bool) -> CenteredFft {
+        let mut fft = CenteredFft { axes: [false;N_DIMS], inverse };
+        axes.iter().for_each(|&a| fft.axes[a] = true);
+        fft
+    }
+
+    fn assert_close(a:&[Complex32], b:&[Complex32]) {
+        assert_eq!(a.len(),b.len());
+        for (a,b) in a.iter().zip(b) {
+            assert!((a - b).norm() < 1e-5,"{} != {}",a,b);
+        }
+    }
+
+    #[test]
+    fn centered_impulse() {
+        // a constant transforms to a scaled impulse at n / 2 and back
+        let dims = ArrayDim::from_shape(&[4]);
+        let mut data = vec![Complex32::ONE;4];
+        along(&[0],false).apply(&mut data,&dims).unwrap();
+        assert_close(&data,&[Complex32::ZERO,Complex32::ZERO,Complex32::new(2.,0.),Complex32::ZERO]);
+        along(&[0],true).apply(&mut data,&dims).unwrap();
+        assert_close(&data,&[Complex32::ONE;4]);
+        // odd lengths center at n / 2 too
+        let mut data = vec![Complex32::ONE;3];
+        along(&[0],false).apply(&mut data,&ArrayDim::from_shape(&[3])).unwrap();
+        assert_close(&data,&[Complex32::ZERO,Complex32::new(3f32.sqrt(),0.),Complex32::ZERO]);
+    }
+
+    #[test]
+    fn strided_axis() {
+        // constant columns of a 2x4 array transform to impulses on row 2
+        let dims = ArrayDim::from_shape(&[2,4]);
+        let mut data:Vec<Complex32> = (0..8).map(|i| Complex32::new((i % 2 + 1) as f32,0.)).collect();
+        along(&[1],false).apply(&mut data,&dims).unwrap();
+        let mut expected = vec![Complex32::ZERO;8];
+        expected[4] = Complex32::new(2.,0.);
+        expected[5] = Complex32::new(4.,0.);
+        assert_close(&data,&expected);
+    }
+
+    #[test]
+    fn round_trip() {
+        let dims = ArrayDim::from_shape(&[4,3,2]);
+        let original:Vec<Complex32> = (0..24).map(|i| Complex32::new((i * 7 % 5) as f32,(i % 3) as f32 - 1.)).collect();
+        let mut data = original.clone();
+        along(&[0,1,2],false).apply(&mut data,&dims).unwrap();
+        // unitary, so the energy is kept
+        let energy = |x:&[Complex32]| x.iter().map(|x| x.norm_sqr()).sum::<f32>();
+        assert!((energy(&data) - energy(&original)).abs() < 1e-3);
+        along(&[0,1,2],true).apply(&mut data,&dims).unwrap();
+        assert_close(&data,&original);
+        assert!(matches!(
+            CenteredFft::default().apply(&mut data[..5],&dims),
+            Err(ViewError::BufferSize { needed: 24, got: 5 })
+        ));
+    }
+}
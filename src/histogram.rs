@@ -0,0 +1,170 @@
+/// default number of histogram bins
+pub const DEFAULT_BINS:usize = 256;
+
+/// counts of scalar values in equal width bins between the smallest and largest value
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    pub min: f32,
+    pub max: f32,
+    pub counts: Vec<usize>,
+}
+
+impl Default for Histogram {
+    fn default() -> Histogram {
+        Histogram {
+            min: 0.,
+            max: 1.,
+            counts: vec![0;DEFAULT_BINS],
+        }
+    }
+}
+
+impl Histogram {
+
+    /// bins the values, ignoring non-finite ones
+    pub fn new(values:&[f32], n_bins:usize) -> Histogram {
+        let n_bins = n_bins.max(1);
+        let (min,max) = values.iter().filter(|x| x.is_finite())
+            .fold((f32::INFINITY,f32::NEG_INFINITY),|(lo,hi),&x| (lo.min(x),hi.max(x)));
+        if min > max {
+            return Histogram { counts: vec![0;n_bins], ..Histogram::default() }
+        }
+        // keep a non-zero range for constant data
+        let max = if max > min { max } else { min + 1. };
+        let mut counts = vec![0;n_bins];
+        let scale = n_bins as f32 / (max - min);
+        for &x in values.iter().filter(|x| x.is_finite()) {
+            let bin = (((x - min) * scale) as usize).min(n_bins - 1);
+            counts[bin] += 1;
+        }
+        Histogram { min, max, counts }
+    }
+
+    pub fn bin_width(&self) -> f32 {
+        (self.max - self.min) / self.counts.len() as f32
+    }
+
+    /// value at the lower edge of a bin
+    pub fn bin_start(&self, bin:usize) -> f32 {
+        self.min + bin as f32 * self.bin_width()
+    }
+
+    pub fn total(&self) -> usize {
+        self.counts.iter().sum()
+    }
+
+    /// the value below which a fraction of the samples fall, resolved to the bin edges
+    pub fn percentile(&self, fraction:f32) -> f32 {
+        let target = fraction.clamp(0.,1.) * self.total() as f32;
+        let mut sum = 0;
+        for (bin,&count) in self.counts.iter().enumerate() {
+            sum += count;
+            if sum as f32 >= target && sum > 0 {
+                return self.bin_start(bin + 1)
+            }
+        }
+        self.max
+    }
+
+    /// window over the central part of the distribution, ignoring outliers
+    pub fn auto_window(&self) -> Window {
+        Window::new(self.percentile(0.01),self.percentile(0.99))
+    }
+
+}
+
+/// range of scalar values mapped to the gray levels from black to white
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Window {
+    pub lo: f32,
+    pub hi: f32,
+}
+
+impl Default for Window {
+    fn default() -> Window {
+        Window::new(0.,u8::MAX as f32 / 128.)
+    }
+}
+
+impl Window {
+
+    /// the bounds may be given in either order
+    pub fn new(a:f32, b:f32) -> Window {
+        Window { lo: a.min(b), hi: a.max(b) }
+    }
+
+    pub fn from_level_width(level:f32, width:f32) -> Window {
+        Window::new(level - width / 2.,level + width / 2.)
+    }
+
+    /// center of the window
+    pub fn level(&self) -> f32 {
+        (self.lo + self.hi) / 2.
+    }
+
+    pub fn width(&self) -> f32 {
+        self.hi - self.lo
+    }
+
+    /// position of a scalar in the window from 0 to 1, clamped at the bounds
+    pub fn normalize(&self, scalar:f32) -> f32 {
+        let width = self.width().max(f32::EPSILON);
+        ((scalar - self.lo) / width).clamp(0.,1.)
+    }
+
+    /// the gray level a scalar is rendered with
+    pub fn display_value(&self, scalar:f32) -> u8 {
+        (self.normalize(scalar) * u8::MAX as f32) as u8
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binning() {
+        let values:Vec<f32> = (0..100).map(|x| x as f32).collect();
+        let histogram = Histogram::new(&values,10);
+        assert_eq!((histogram.min,histogram.max),(0.,99.));
+        assert_eq!(histogram.counts,[10;10]);
+        assert_eq!(histogram.bin_width(),9.9);
+        assert_eq!(histogram.bin_start(0),0.);
+        // non-finite values are ignored and constant data keeps a unit range
+        let histogram = Histogram::new(&[5.,f32::NAN,5.,f32::INFINITY],4);
+        assert_eq!((histogram.min,histogram.max),(5.,6.));
+        assert_eq!(histogram.counts,[2,0,0,0]);
+        let histogram = Histogram::new(&[],0);
+        assert_eq!(histogram.counts,[0]);
+        assert_eq!(histogram.total(),0);
+    }
+
+    #[test]
+    fn auto_window_ignores_outliers() {
+        let mut values:Vec<f32> = (0..100).map(|x| x as f32).collect();
+        values.push(1000.);
+        let histogram = Histogram::new(&values,100);
+        assert_eq!(histogram.counts[0],10);
+        assert_eq!(histogram.counts[99],1);
+        assert_eq!(histogram.percentile(0.),10.);
+        assert_eq!(histogram.percentile(1.),1000.);
+        assert_eq!(histogram.auto_window(),Window::new(10.,100.));
+    }
+
+    #[test]
+    fn window() {
+        let window = Window::new(2.,0.);
+        assert_eq!((window.lo,window.hi),(0.,2.));
+        assert_eq!((window.level(),window.width()),(1.,2.));
+        assert_eq!(Window::from_level_width(1.,2.),window);
+        assert_eq!(window.normalize(0.5),0.25);
+        assert_eq!(window.normalize(-1.),0.);
+        assert_eq!(window.display_value(1.),127);
+        assert_eq!(window.display_value(3.),255);
+        // a zero width window is a threshold
+        let threshold = Window::new(1.,1.);
+        assert_eq!(threshold.display_value(0.5),0);
+        assert_eq!(threshold.display_value(1.5),255);
+    }
+}
@@ -0,0 +1,234 @@
+use std::path::Path;
+use array_lib::ArrayDim;
+use array_lib::cfl::num_complex::Complex32;
+use crate::cfl_buffer::CflBuffer;
+use crate::slice::N_DIMS;
+use crate::ViewError;
+
+/// cfl dimensions raw data is arranged along, in bart's dimension order
+pub const READ_DIM:usize = 0;
+pub const PHS1_DIM:usize = 1;
+pub const PHS2_DIM:usize = 2;
+pub const COIL_DIM:usize = 3;
+pub const TE_DIM:usize = 5;
+pub const TIME_DIM:usize = 10;
+pub const TIME2_DIM:usize = 11;
+pub const SLICE_DIM:usize = 13;
+pub const AVG_DIM:usize = 14;
+
+/// acquisition flags of lines that aren't part of the k-space, as bit positions. Flag n is bit n - 1
+const NOISE_MEASUREMENT_FLAG:u32 = 19;
+const NAVIGATION_FLAG:u32 = 23;
+const PHASECORR_FLAG:u32 = 24;
+const HPFEEDBACK_FLAG:u32 = 26;
+const DUMMYSCAN_FLAG:u32 = 27;
+const RTFEEDBACK_FLAG:u32 = 28;
+
+/// the encoding counters of an acquisition header that place a line in k-space
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EncodingIndex {
+    pub kspace_encode_step_1: usize,
+    pub kspace_encode_step_2: usize,
+    pub slice: usize,
+    pub contrast: usize,
+    pub phase: usize,
+    pub repetition: usize,
+    pub average: usize,
+}
+
+impl EncodingIndex {
+
+    /// the cfl index of the first sample of the line
+    pub fn cfl_index(&self) -> [usize;N_DIMS] {
+        let mut idx = [0;N_DIMS];
+        idx[PHS1_DIM] = self.kspace_encode_step_1;
+        idx[PHS2_DIM] = self.kspace_encode_step_2;
+        idx[SLICE_DIM] = self.slice;
+        idx[TE_DIM] = self.contrast;
+        idx[TIME2_DIM] = self.phase;
+        idx[TIME_DIM] = self.repetition;
+        idx[AVG_DIM] = self.average;
+        idx
+    }
+
+}
+
+/// one readout of every active channel
+#[derive(Debug, Clone, PartialEq)]
+pub struct AcquisitionLine {
+    pub index: EncodingIndex,
+    pub samples: usize,
+    pub channels: usize,
+    /// the samples of the first channel, followed by those of the next
+    pub data: Vec<Complex32>,
+}
+
+/// places lines into a k-space array sized to hold all of them. Lines shorter than the longest readout start
+/// at the first sample, and a line landing on an index that is already filled replaces it
+pub fn assemble(lines:&[AcquisitionLine]) -> Result<CflBuffer,ViewError> {
+    let mut shape = [1;N_DIMS];
+    for line in lines {
+        if line.data.len() < line.samples * line.channels {
+            return Err(ViewError::BufferSize { needed: line.samples * line.channels, got: line.data.len() });
+        }
+        let idx = line.index.cfl_index();
+        for (size,i) in shape.iter_mut().zip(idx) {
+            *size = (*size).max(i + 1);
+        }
+        shape[READ_DIM] = shape[READ_DIM].max(line.samples);
+        shape[COIL_DIM] = shape[COIL_DIM].max(line.channels);
+    }
+    let dims = ArrayDim::from_shape(&shape);
+    let strides = dims.strides();
+    let mut data = vec![Complex32::ZERO;dims.numel()];
+    for line in lines {
+        let start = dims.calc_addr(&line.index.cfl_index());
+        for (channel,samples) in line.data.chunks_exact(line.samples.max(1)).take(line.channels).enumerate() {
+            let start = start + channel * strides[COIL_DIM];
+            for (k,&x) in samples.iter().enumerate() {
+                data[start + k * strides[READ_DIM]] = x;
+            }
+        }
+    }
+    Ok(CflBuffer::new(data,dims))
+}
+
+/// true for acquisitions that belong in the k-space, leaving out noise scans, navigators, phase correction
+/// lines, dummy scans and feedback lines
+pub fn is_imaging(flags:u64) -> bool {
+    [NOISE_MEASUREMENT_FLAG,NAVIGATION_FLAG,PHASECORR_FLAG,HPFEEDBACK_FLAG,DUMMYSCAN_FLAG,RTFEEDBACK_FLAG].iter()
+        .all(|flag| flags & (1 << (flag - 1)) == 0)
+}
+
+/// the fields of the ismrmrd encoding counters the viewer uses. Hdf5 matches compound fields by name, so
+/// the rest of the counters are skipped when reading
+#[cfg(feature = "hdf5")]
+#[derive(hdf5::H5Type, Clone, Copy)]
+#[repr(C)]
+struct EncodingCounters {
+    kspace_encode_step_1: u16,
+    kspace_encode_step_2: u16,
+    average: u16,
+    slice: u16,
+    contrast: u16,
+    phase: u16,
+    repetition: u16,
+}
+
+#[cfg(feature = "hdf5")]
+#[derive(hdf5::H5Type, Clone, Copy)]
+#[repr(C)]
+struct AcquisitionHeader {
+    flags: u64,
+    number_of_samples: u16,
+    active_channels: u16,
+    idx: EncodingCounters,
+}
+
+#[cfg(feature = "hdf5")]
+#[derive(hdf5::H5Type, Clone)]
+#[repr(C)]
+struct Acquisition {
+    head: AcquisitionHeader,
+    /// interleaved real and imaginary parts
+    data: hdf5::types::VarLenArray<f32>,
+}
+
+/// reads the acquisitions of an ismrmrd file into a k-space array, placing each line by its encoding
+/// counters. The acquisitions are read from /dataset/data, or from the first group holding a data dataset
+#[cfg(feature = "hdf5")]
+pub fn read_ismrmrd(path:impl AsRef<Path>) -> Result<CflBuffer,ViewError> {
+    let hdf5_error = |e:hdf5::Error| ViewError::Parse(e.to_string());
+    let file = hdf5::File::open(path).map_err(hdf5_error)?;
+    let group = match file.group("dataset") {
+        Ok(group) => group,
+        Err(_) => file.member_names().map_err(hdf5_error)?.iter()
+            .filter_map(|name| file.group(name).ok())
+            .find(|group| group.link_exists("data"))
+            .ok_or(ViewError::Parse("no ismrmrd dataset in file".to_string()))?,
+    };
+    let acquisitions = group.dataset("data").and_then(|data| data.read_raw::<Acquisition>()).map_err(hdf5_error)?;
+    let lines:Vec<AcquisitionLine> = acquisitions.iter()
+        .filter(|acquisition| is_imaging(acquisition.head.flags))
+        .map(|acquisition|{
+            let head = &acquisition.head;
+            let idx = &head.idx;
+            AcquisitionLine {
+                index: EncodingIndex {
+                    kspace_encode_step_1: idx.kspace_encode_step_1 as usize,
+                    kspace_encode_step_2: idx.kspace_encode_step_2 as usize,
+                    slice: idx.slice as usize,
+                    contrast: idx.contrast as usize,
+                    phase: idx.phase as usize,
+                    repetition: idx.repetition as usize,
+                    average: idx.average as usize,
+                },
+                samples: head.number_of_samples as usize,
+                channels: head.active_channels as usize,
+                data: acquisition.data.chunks_exact(2).map(|x| Complex32::new(x[0],x[1])).collect(),
+            }
+        })
+        .collect();
+    assemble(&lines)
+}
+
+#[cfg(not(feature = "hdf5"))]
+pub fn read_ismrmrd(_path:impl AsRef<Path>) -> Result<CflBuffer,ViewError> {
+    Err(ViewError::Parse("reading ismrmrd files needs the hdf5 feature".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a line whose samples encode their channel and sample number
+    fn line(index:EncodingIndex, samples:usize, channels:usize) -> AcquisitionLine {
+        let data = (0..channels).flat_map(|c| (0..samples).map(move |k| Complex32::new(k as f32,c as f32))).collect();
+        AcquisitionLine { index, samples, channels, data }
+    }
+
+    #[test]
+    fn assemble_places_lines() {
+        let lines = [
+            line(EncodingIndex { kspace_encode_step_1: 3, slice: 1, ..EncodingIndex::default() },4,2),
+            // a shorter readout of another contrast and average
+            line(EncodingIndex { kspace_encode_step_1: 1, contrast: 2, average: 1, ..EncodingIndex::default() },2,2),
+        ];
+        let cfl_buffer = assemble(&lines).unwrap();
+        let shape = cfl_buffer.dims.shape();
+        assert_eq!(
+            (shape[READ_DIM],shape[PHS1_DIM],shape[COIL_DIM],shape[TE_DIM],shape[SLICE_DIM],shape[AVG_DIM]),
+            (4,4,2,3,2,2)
+        );
+        let at = |k:usize, channel:usize, index:EncodingIndex| {
+            let mut idx = index.cfl_index();
+            idx[READ_DIM] = k;
+            idx[COIL_DIM] = channel;
+            cfl_buffer.get(&idx).unwrap()
+        };
+        assert_eq!(at(3,1,lines[0].index),Complex32::new(3.,1.));
+        assert_eq!(at(1,0,lines[0].index),Complex32::new(1.,0.));
+        assert_eq!(at(1,1,lines[1].index),Complex32::new(1.,1.));
+        // the short readout starts at the first sample and leaves the rest zero
+        assert_eq!(at(2,1,lines[1].index),Complex32::ZERO);
+        assert_eq!(cfl_buffer.data.iter().filter(|x| **x != Complex32::ZERO).count(),4 * 2 + 2 * 2 - 2);
+    }
+
+    #[test]
+    fn assemble_rejects_short_data() {
+        let mut short = line(EncodingIndex::default(),4,2);
+        short.data.pop();
+        assert!(matches!(assemble(&[short]),Err(ViewError::BufferSize { needed: 8, got: 7 })));
+    }
+
+    #[test]
+    fn imaging_flags() {
+        let flag = |n:u32| 1u64 << (n - 1);
+        assert!(is_imaging(0));
+        // calibration and imaging, reverse and last in measurement lines hold k-space
+        assert!(is_imaging(flag(21) | flag(22) | flag(25)));
+        for n in [19,23,24,26,27,28] {
+            assert!(!is_imaging(flag(n)),"flag {}",n);
+        }
+    }
+}
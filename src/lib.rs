@@ -1,4 +1,49 @@
 pub mod view_panel;
+pub mod cfl_buffer;
+pub mod slice;
+pub mod reslice;
+pub mod projection;
+pub mod cine;
+pub mod roi;
+pub mod profile;
+pub mod histogram;
+pub mod fft;
+pub mod difference;
+pub mod export;
+pub mod nifti;
+pub mod raw;
+pub mod npy;
+pub mod mat;
+pub mod ismrmrd;
+pub mod dicom;
+
+#[derive(Debug)]
+pub enum ViewError {
+    /// a dimension that doesn't exist in the cfl
+    BadIndex(usize),
+    /// a slice index past the end of its dimension
+    IndexOutOfBounds { dim:usize, index:usize, size:usize },
+    /// an output buffer that is too small for the requested data
+    BufferSize { needed:usize, got:usize },
+    /// a plane with a degenerate normal
+    BadPlane,
+    /// a file that couldn't be read or written
+    Io(std::io::Error),
+    /// a value that couldn't be parsed
+    Parse(String),
+    /// an image that couldn't be encoded
+    Encode(String),
+    /// an operation the pane or file it was asked of doesn't support
+    Unsupported(&'static str),
+    /// a region that covers no samples
+    EmptySelection,
+}
+
+impl From<std::io::Error> for ViewError {
+    fn from(e: std::io::Error) -> ViewError {
+        ViewError::Io(e)
+    }
+}
 
 
 // use std::fmt::{Debug, Formatter};
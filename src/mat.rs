@@ -0,0 +1,444 @@
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use array_lib::ArrayDim;
+use array_lib::cfl::num_complex::Complex32;
+use flate2::read::ZlibDecoder;
+use crate::cfl_buffer::CflBuffer;
+use crate::raw::{le, Endianness, RawType};
+use crate::slice::N_DIMS;
+use crate::ViewError;
+
+/// size of the text header of a .mat file
+const MAT_HEADER:usize = 128;
+/// v7.3 files are hdf5 files with the .mat header in a 512 byte user block
+const HDF5_SIGNATURE:&[u8] = b"\x89HDF\r\n\x1a\n";
+const HDF5_USER_BLOCK:usize = 512;
+
+const MI_MATRIX:u32 = 14;
+const MI_COMPRESSED:u32 = 15;
+
+/// a numeric array stored in a .mat file
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatVariable {
+    pub name: String,
+    /// size along each dimension in matlab order, which is the cfl order
+    pub shape: Vec<usize>,
+    pub complex: bool,
+    /// matlab class, such as double or int16
+    pub class: String,
+}
+
+impl Display for MatVariable {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let shape:Vec<String> = self.shape.iter().map(|n| n.to_string()).collect();
+        let complex = if self.complex { "complex " } else { "" };
+        write!(f, "{} {} {}{}", self.name, shape.join("x"), complex, self.class)
+    }
+}
+
+/// lists the numeric variables of a v5 or v7.3 .mat file without reading their data. Cells, structs, strings,
+/// logical, empty and sparse arrays are left out
+pub fn list_mat(path:impl AsRef<Path>) -> Result<Vec<MatVariable>,ViewError> {
+    if is_hdf5(path.as_ref())? {
+        return list_v73(path.as_ref())
+    }
+    let bytes = std::fs::read(path)?;
+    let mut variables = vec![];
+    v5_arrays(&bytes,|array| {
+        variables.push(array.variable);
+        Ok(())
+    })?;
+    Ok(variables)
+}
+
+/// reads a numeric variable of a .mat file. Matlab arrays are column-major like a cfl, so dimension i of the
+/// variable becomes cfl dimension i
+pub fn read_mat(path:impl AsRef<Path>, name:&str) -> Result<CflBuffer,ViewError> {
+    read_mat_variables(path.as_ref(),Some(name))?.into_iter().next()
+        .map(|(_,cfl_buffer)| cfl_buffer)
+        .ok_or(ViewError::Parse(format!("no numeric variable {}",name)))
+}
+
+/// reads every numeric variable of a .mat file with its name, parsing the file once
+pub fn read_mat_all(path:impl AsRef<Path>) -> Result<Vec<(String,CflBuffer)>,ViewError> {
+    read_mat_variables(path.as_ref(),None)
+}
+
+/// reads the numeric variables of a .mat file, or only the one named
+fn read_mat_variables(path:&Path, name:Option<&str>) -> Result<Vec<(String,CflBuffer)>,ViewError> {
+    if is_hdf5(path)? {
+        return read_v73(path,name)
+    }
+    let bytes = std::fs::read(path)?;
+    let endianness = if v5_big_endian(&bytes)? { Endianness::Big } else { Endianness::Little };
+    let mut arrays = vec![];
+    v5_arrays(&bytes,|array| {
+        if name.is_some_and(|name| name != array.variable.name) {
+            return Ok(())
+        }
+        let real = v5_values(array.real,endianness)?;
+        let data:Vec<Complex32> = match array.imag {
+            Some(imag) => real.iter().zip(v5_values(imag,endianness)?).map(|(&re,im)| Complex32::new(re,im)).collect(),
+            None => real.into_iter().map(|re| Complex32::new(re,0.)).collect(),
+        };
+        let dims = ArrayDim::from_shape(&array.variable.shape);
+        if data.len() < dims.numel() {
+            return Err(ViewError::BufferSize { needed: dims.numel(), got: data.len() });
+        }
+        arrays.push((array.variable.name,CflBuffer::new(data,dims)));
+        Ok(())
+    })?;
+    Ok(arrays)
+}
+
+/// checks for the hdf5 signature of a v7.3 file without reading the whole file
+fn is_hdf5(path:&Path) -> Result<bool,ViewError> {
+    let mut start = vec![];
+    File::open(path)?.take((HDF5_USER_BLOCK + HDF5_SIGNATURE.len()) as u64).read_to_end(&mut start)?;
+    Ok(start.starts_with(HDF5_SIGNATURE) || start.get(HDF5_USER_BLOCK..).is_some_and(|block| block.starts_with(HDF5_SIGNATURE)))
+}
+
+/// a v5 matrix with its data still packed
+struct V5Array<'a> {
+    variable: MatVariable,
+    /// data element type and bytes of the real part
+    real: (u32,&'a [u8]),
+    imag: Option<(u32,&'a [u8])>,
+}
+
+/// the header ends with 'MI' written as a 16-bit value, which reads back as 'IM' on a little endian machine
+fn v5_big_endian(bytes:&[u8]) -> Result<bool,ViewError> {
+    match bytes.get(MAT_HEADER - 2..MAT_HEADER) {
+        Some(b"IM") => Ok(false),
+        Some(b"MI") => Ok(true),
+        _ => Err(ViewError::Parse("not a .mat file".to_string())),
+    }
+}
+
+/// calls f with each numeric matrix of a v5 file in turn. Compressed elements are inflated one at a time, so
+/// only one of them is held in memory
+fn v5_arrays(bytes:&[u8], mut f:impl FnMut(V5Array) -> Result<(),ViewError>) -> Result<(),ViewError> {
+    let big_endian = v5_big_endian(bytes)?;
+    let mut pos = MAT_HEADER;
+    while pos + 8 <= bytes.len() {
+        let (data_type,data,next) = v5_element(bytes,pos,big_endian)?;
+        pos = next;
+        match data_type {
+            MI_COMPRESSED => {
+                let mut inflated = vec![];
+                ZlibDecoder::new(data).read_to_end(&mut inflated)?;
+                let (data_type,data,_) = v5_element(&inflated,0,big_endian)?;
+                if data_type == MI_MATRIX && let Some(array) = v5_matrix(data,big_endian)? {
+                    f(array)?;
+                }
+            }
+            MI_MATRIX => if let Some(array) = v5_matrix(data,big_endian)? {
+                f(array)?;
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// the type and data of the element at pos, with the position of the next element
+fn v5_element(bytes:&[u8], pos:usize, big_endian:bool) -> Result<(u32,&[u8],usize),ViewError> {
+    let truncated = || ViewError::Parse("truncated .mat element".to_string());
+    let word = |at:usize| bytes.get(at..at + 4).map(|b| u32::from_le_bytes(le(b,big_endian))).ok_or_else(truncated);
+    let tag = word(pos)?;
+    // small elements pack the size into the upper half of the tag and the data into the next 4 bytes
+    if tag >> 16 != 0 {
+        let n = (tag >> 16) as usize;
+        let data = bytes.get(pos + 4..pos + 4 + n.min(4)).ok_or_else(truncated)?;
+        return Ok((tag & 0xffff,data,pos + 8))
+    }
+    let n = word(pos + 4)? as usize;
+    let data = bytes.get(pos + 8..pos + 8 + n).ok_or_else(truncated)?;
+    // compressed elements aren't padded to 8 bytes
+    let next = if tag == MI_COMPRESSED { pos + 8 + n } else { pos + 8 + n.div_ceil(8) * 8 };
+    Ok((tag,data,next))
+}
+
+/// parses the sub-elements of a matrix. Only numeric classes are returned, leaving out logical and empty arrays
+fn v5_matrix(data:&[u8], big_endian:bool) -> Result<Option<V5Array<'_>>,ViewError> {
+    let (_,flags,next) = v5_element(data,0,big_endian)?;
+    let flags = flags.get(..4).map(|b| u32::from_le_bytes(le(b,big_endian))).unwrap_or(0);
+    let Some(class) = class_name(flags & 0xff) else {
+        return Ok(None)
+    };
+    if flags & 0x200 != 0 {
+        return Ok(None)
+    }
+    let complex = flags & 0x800 != 0;
+    let (_,dims,next) = v5_element(data,next,big_endian)?;
+    let shape:Vec<usize> = dims.chunks_exact(4).map(|b| i32::from_le_bytes(le(b,big_endian)).max(0) as usize).collect();
+    if shape.contains(&0) {
+        return Ok(None)
+    }
+    if shape.len() > N_DIMS {
+        return Err(ViewError::BadIndex(shape.len()));
+    }
+    let (_,name,next) = v5_element(data,next,big_endian)?;
+    let name = String::from_utf8_lossy(name).trim_end_matches('\0').to_string();
+    let (real_type,real,next) = v5_element(data,next,big_endian)?;
+    let imag = if complex {
+        let (imag_type,imag,_) = v5_element(data,next,big_endian)?;
+        Some((imag_type,imag))
+    }else {
+        None
+    };
+    Ok(Some(V5Array {
+        variable: MatVariable { name, shape, complex, class: class.to_string() },
+        real: (real_type,real),
+        imag,
+    }))
+}
+
+/// numeric matlab classes by their class id
+fn class_name(class:u32) -> Option<&'static str> {
+    match class {
+        6 => Some("double"),
+        7 => Some("single"),
+        8 => Some("int8"),
+        9 => Some("uint8"),
+        10 => Some("int16"),
+        11 => Some("uint16"),
+        12 => Some("int32"),
+        13 => Some("uint32"),
+        14 => Some("int64"),
+        15 => Some("uint64"),
+        _ => None,
+    }
+}
+
+/// converts packed element data to f32. Matlab may store data in a smaller type than its class
+fn v5_values((data_type,bytes):(u32,&[u8]), endianness:Endianness) -> Result<Vec<f32>,ViewError> {
+    let dtype = match data_type {
+        1 => RawType::I8,
+        2 => RawType::U8,
+        3 => RawType::I16,
+        4 => RawType::U16,
+        5 => RawType::I32,
+        6 => RawType::U32,
+        7 => RawType::F32,
+        9 => RawType::F64,
+        12 => RawType::I64,
+        13 => RawType::U64,
+        _ => return Err(ViewError::Parse(format!("unsupported .mat data type {}",data_type))),
+    };
+    Ok(dtype.decode(bytes,endianness))
+}
+
+/// complex matlab arrays are stored in hdf5 as a compound of real and imaginary parts
+#[cfg(feature = "hdf5")]
+#[derive(hdf5::H5Type, Clone, Copy)]
+#[repr(C)]
+struct MatComplex {
+    real: f32,
+    imag: f32,
+}
+
+#[cfg(feature = "hdf5")]
+fn hdf5_error(e:hdf5::Error) -> ViewError {
+    ViewError::Parse(e.to_string())
+}
+
+/// describes a dataset if it holds a real or complex numeric array. Matlab tags each variable with its class
+/// in a MATLAB_class attribute, as strings and logicals are stored as integers, and empty arrays hold their
+/// dimensions in place of data and are tagged with MATLAB_empty
+#[cfg(feature = "hdf5")]
+fn v73_variable(name:&str, dataset:&hdf5::Dataset) -> Option<MatVariable> {
+    use hdf5::types::{FixedAscii, TypeDescriptor};
+    let class = dataset.attr("MATLAB_class").ok()?.read_scalar::<FixedAscii<32>>().ok()?.as_str().to_string();
+    if !(6..=15).filter_map(class_name).any(|numeric| numeric == class) {
+        return None
+    }
+    if dataset.attr("MATLAB_empty").and_then(|empty| empty.read_scalar::<u8>()).is_ok_and(|empty| empty != 0) {
+        return None
+    }
+    let complex = match dataset.dtype().and_then(|dtype| dtype.to_descriptor()).ok()? {
+        TypeDescriptor::Integer(_) | TypeDescriptor::Unsigned(_) | TypeDescriptor::Float(_) => false,
+        TypeDescriptor::Compound(compound) if compound.fields.iter().map(|field| field.name.as_str()).eq(["real","imag"]) => true,
+        _ => return None,
+    };
+    // hdf5 shapes are row-major, so matlab's dimensions appear reversed
+    let shape:Vec<usize> = dataset.shape().into_iter().rev().collect();
+    if shape.len() > N_DIMS {
+        return None
+    }
+    Some(MatVariable { name: name.to_string(), shape, complex, class })
+}
+
+/// the numeric variables of a v7.3 file with their datasets. Structs and cells are groups or references, and
+/// matlab keeps its own data in #refs# and #subsystem#
+#[cfg(feature = "hdf5")]
+fn v73_variables(file:&hdf5::File) -> Result<Vec<(MatVariable,hdf5::Dataset)>,ViewError> {
+    let names = file.member_names().map_err(hdf5_error)?;
+    Ok(names.iter()
+        .filter(|name| !name.starts_with('#'))
+        .filter_map(|name|{
+            let dataset = file.dataset(name).ok()?;
+            Some((v73_variable(name,&dataset)?,dataset))
+        })
+        .collect())
+}
+
+#[cfg(feature = "hdf5")]
+fn list_v73(path:&Path) -> Result<Vec<MatVariable>,ViewError> {
+    let file = hdf5::File::open(path).map_err(hdf5_error)?;
+    Ok(v73_variables(&file)?.into_iter().map(|(variable,_)| variable).collect())
+}
+
+#[cfg(feature = "hdf5")]
+fn read_v73(path:&Path, name:Option<&str>) -> Result<Vec<(String,CflBuffer)>,ViewError> {
+    let file = hdf5::File::open(path).map_err(hdf5_error)?;
+    v73_variables(&file)?.into_iter()
+        .filter(|(variable,_)| name.is_none_or(|name| name == variable.name))
+        .map(|(variable,dataset)|{
+            let data:Vec<Complex32> = if variable.complex {
+                dataset.read_raw::<MatComplex>().map_err(hdf5_error)?.into_iter().map(|x| Complex32::new(x.real,x.imag)).collect()
+            }else {
+                dataset.read_raw::<f32>().map_err(hdf5_error)?.into_iter().map(|x| Complex32::new(x,0.)).collect()
+            };
+            Ok((variable.name,CflBuffer::new(data,ArrayDim::from_shape(&variable.shape))))
+        })
+        .collect()
+}
+
+#[cfg(not(feature = "hdf5"))]
+fn list_v73(_path:&Path) -> Result<Vec<MatVariable>,ViewError> {
+    Err(ViewError::Parse("reading v7.3 .mat files needs the hdf5 feature".to_string()))
+}
+
+#[cfg(not(feature = "hdf5"))]
+fn read_v73(_path:&Path, _name:Option<&str>) -> Result<Vec<(String,CflBuffer)>,ViewError> {
+    Err(ViewError::Parse("reading v7.3 .mat files needs the hdf5 feature".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::path::PathBuf;
+    use flate2::write::ZlibEncoder;
+
+    const MI_INT8:u32 = 1;
+    const MI_INT16:u32 = 3;
+    const MI_UINT16:u32 = 4;
+    const MI_INT32:u32 = 5;
+    const MI_UINT32:u32 = 6;
+    const MI_SINGLE:u32 = 7;
+
+    /// writes a .mat file in the given byte order from its elements
+    struct MatWriter {
+        big_endian: bool,
+    }
+
+    impl MatWriter {
+
+        fn word(&self, x:u32) -> [u8;4] {
+            if self.big_endian { x.to_be_bytes() } else { x.to_le_bytes() }
+        }
+
+        fn file(&self, elements:&[Vec<u8>]) -> Vec<u8> {
+            let mut bytes = vec![b' ';MAT_HEADER - 4];
+            bytes.extend(if self.big_endian { 0x0100u16.to_be_bytes() } else { 0x0100u16.to_le_bytes() });
+            bytes.extend(if self.big_endian { b"MI" } else { b"IM" });
+            bytes.extend(elements.concat());
+            bytes
+        }
+
+        /// an element with an 8 byte tag, padded to 8 bytes
+        fn element(&self, data_type:u32, data:&[u8]) -> Vec<u8> {
+            let mut bytes = [self.word(data_type),self.word(data.len() as u32)].concat();
+            bytes.extend(data);
+            bytes.resize(bytes.len().div_ceil(8) * 8,0);
+            bytes
+        }
+
+        /// an element of up to 4 bytes packed with its size into a 4 byte tag
+        fn small(&self, data_type:u32, data:&[u8]) -> Vec<u8> {
+            let mut bytes = self.word(data_type | (data.len() as u32) << 16).to_vec();
+            bytes.extend(data);
+            bytes.resize(8,0);
+            bytes
+        }
+
+        fn matrix(&self, name:&str, flags:u32, shape:&[i32], parts:&[Vec<u8>]) -> Vec<u8> {
+            let dims:Vec<u8> = shape.iter().flat_map(|&n| self.word(n as u32)).collect();
+            let mut data = [self.element(MI_UINT32,&[self.word(flags),[0;4]].concat()),self.element(MI_INT32,&dims)].concat();
+            data.extend(if name.len() <= 4 { self.small(MI_INT8,name.as_bytes()) } else { self.element(MI_INT8,name.as_bytes()) });
+            data.extend(parts.concat());
+            self.element(MI_MATRIX,&data)
+        }
+
+        fn compressed(&self, element:&[u8]) -> Vec<u8> {
+            let mut encoder = ZlibEncoder::new(vec![],flate2::Compression::default());
+            encoder.write_all(element).unwrap();
+            let data = encoder.finish().unwrap();
+            let mut bytes = [self.word(MI_COMPRESSED),self.word(data.len() as u32)].concat();
+            bytes.extend(data);
+            bytes
+        }
+
+    }
+
+    fn write_temp(name:&str, bytes:&[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("cfl_view_mat_{}_{}.mat",name,std::process::id()));
+        std::fs::write(&path,bytes).unwrap();
+        path
+    }
+
+    #[test]
+    fn small_elements_and_complex() {
+        let w = MatWriter { big_endian: false };
+        let real:Vec<u8> = [1i16,-2].iter().flat_map(|x| x.to_le_bytes()).collect();
+        let bytes = w.file(&[
+            // a complex double stored as int16 and int8, both packed into small elements
+            w.matrix("x",6 | 0x800,&[2,1],&[w.small(MI_INT16,&real),w.small(MI_INT8,&[3,4])]),
+            // a string, a logical and an empty double are skipped
+            w.matrix("s",4,&[1,2],&[w.small(MI_UINT16,&[104,0,105,0])]),
+            w.matrix("b",9 | 0x200,&[1,1],&[w.small(2,&[1])]),
+            w.matrix("e",6,&[0,0],&[]),
+        ]);
+        let path = write_temp("small",&bytes);
+        let variables = list_mat(&path).unwrap();
+        assert_eq!(variables,vec![MatVariable { name: "x".to_string(), shape: vec![2,1], complex: true, class: "double".to_string() }]);
+        let x = read_mat(&path,"x").unwrap();
+        assert_eq!(x.data,vec![Complex32::new(1.,3.),Complex32::new(-2.,4.)]);
+        assert!(read_mat(&path,"s").is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn compressed_elements() {
+        let w = MatWriter { big_endian: false };
+        let values:Vec<u8> = [0.5f32,1.5,2.5].iter().flat_map(|x| x.to_le_bytes()).collect();
+        let bytes = w.file(&[
+            w.compressed(&w.matrix("long_name",7,&[1,3],&[w.element(MI_SINGLE,&values)])),
+            w.matrix("y",6,&[1,1],&[w.small(MI_INT8,&[7])]),
+        ]);
+        let path = write_temp("compressed",&bytes);
+        let arrays = read_mat_all(&path).unwrap();
+        let names:Vec<&str> = arrays.iter().map(|(name,_)| name.as_str()).collect();
+        assert_eq!(names,["long_name","y"]);
+        assert_eq!(arrays[0].1.dims.shape()[..2],[1,3]);
+        assert_eq!(arrays[0].1.data,vec![Complex32::new(0.5,0.),Complex32::new(1.5,0.),Complex32::new(2.5,0.)]);
+        assert_eq!(read_mat(&path,"y").unwrap().data,vec![Complex32::new(7.,0.)]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn big_endian() {
+        let w = MatWriter { big_endian: true };
+        let values:Vec<u8> = [1u16,2,3,258].iter().flat_map(|x| x.to_be_bytes()).collect();
+        let bytes = w.file(&[w.matrix("z",11,&[2,2],&[w.element(MI_UINT16,&values)])]);
+        let path = write_temp("big_endian",&bytes);
+        assert_eq!(list_mat(&path).unwrap()[0].to_string(),"z 2x2 uint16");
+        let z = read_mat(&path,"z").unwrap();
+        assert_eq!(z.data,[1.,2.,3.,258.].map(|x| Complex32::new(x,0.)).to_vec());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
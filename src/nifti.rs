@@ -0,0 +1,513 @@
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use array_lib::ArrayDim;
+use array_lib::cfl::num_complex::Complex32;
+use flate2::Compression;
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use crate::cfl_buffer::CflBuffer;
+use crate::raw::{le, Endianness, RawType};
+use crate::slice::N_DIMS;
+use crate::ViewError;
+
+/// size of a nifti-1 header
+const NIFTI1_HEADER:usize = 348;
+/// size of a nifti-2 header
+const NIFTI2_HEADER:usize = 540;
+/// nifti can hold at most 7 dimensions
+const NIFTI_DIMS:usize = 7;
+
+const DT_UINT8:i16 = 2;
+const DT_INT16:i16 = 4;
+const DT_INT32:i16 = 8;
+const DT_FLOAT32:i16 = 16;
+const DT_COMPLEX64:i16 = 32;
+const DT_FLOAT64:i16 = 64;
+const DT_INT8:i16 = 256;
+const DT_UINT16:i16 = 512;
+const DT_UINT32:i16 = 768;
+const DT_INT64:i16 = 1024;
+const DT_UINT64:i16 = 1280;
+const DT_COMPLEX128:i16 = 1792;
+
+/// how complex samples are stored when writing nifti, since most nifti tools only read real volumes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NiftiComponents {
+    Magnitude,
+    /// magnitude and phase in radians as two files, name_mag and name_phase
+    MagnitudePhase,
+    /// real and imaginary parts as two files, name_real and name_imag
+    RealImaginary,
+    /// a single complex64 volume
+    Complex,
+}
+
+impl NiftiComponents {
+    pub const ALL: [NiftiComponents;4] = [
+        NiftiComponents::Magnitude,
+        NiftiComponents::MagnitudePhase,
+        NiftiComponents::RealImaginary,
+        NiftiComponents::Complex,
+    ];
+}
+
+impl Display for NiftiComponents {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NiftiComponents::Magnitude => write!(f, "magnitude"),
+            NiftiComponents::MagnitudePhase => write!(f, "magnitude + phase"),
+            NiftiComponents::RealImaginary => write!(f, "real + imaginary"),
+            NiftiComponents::Complex => write!(f, "complex"),
+        }
+    }
+}
+
+/// true for .nii and .nii.gz paths
+pub fn is_nifti(path:impl AsRef<Path>) -> bool {
+    let name = path.as_ref().file_name().map(|name| name.to_string_lossy().to_lowercase()).unwrap_or_default();
+    name.ends_with(".nii") || name.ends_with(".nii.gz")
+}
+
+/// the real value written for a complex sample
+type Component = fn(Complex32) -> f32;
+
+/// the fields of a nifti-1 or nifti-2 header the viewer uses
+struct Header {
+    dim: [i64;8],
+    datatype: i16,
+    pixdim: [f64;8],
+    vox_offset: u64,
+    scl_slope: f64,
+    scl_inter: f64,
+    xyzt_units: u8,
+    qform_code: i32,
+    sform_code: i32,
+    /// quatern_b, c and d
+    quatern: [f64;3],
+    qoffset: [f64;3],
+    srow: [[f64;4];3],
+}
+
+/// reads a nifti-1 or nifti-2 file, gzipped or not. Nifti dimensions fill the cfl dimensions in order, so
+/// x, y and z land on 0, 1 and 2 and time on 3. Voxel sizes and orientation are taken from the header, with
+/// the sform preferred over the qform
+pub fn read_nifti(path:impl AsRef<Path>) -> Result<CflBuffer,ViewError> {
+    let mut bytes = vec![];
+    let mut file = File::open(path)?;
+    file.read_to_end(&mut bytes)?;
+    if bytes.starts_with(&[0x1f,0x8b]) {
+        let mut decoded = vec![];
+        MultiGzDecoder::new(bytes.as_slice()).read_to_end(&mut decoded)?;
+        bytes = decoded;
+    }
+
+    let (header,big_endian) = parse_header(&bytes)?;
+    let n_dims = header.dim[0];
+    if !(1..=NIFTI_DIMS as i64).contains(&n_dims) {
+        return Err(ViewError::Parse(format!("nifti with {} dimensions",n_dims)));
+    }
+    let mut shape = [1;N_DIMS];
+    for (i,size) in shape.iter_mut().enumerate().take(n_dims as usize) {
+        *size = usize::try_from(header.dim[i + 1]).map_err(|_| ViewError::Parse(format!("nifti dim {}",header.dim[i + 1])))?.max(1);
+    }
+    let dims = ArrayDim::from_shape(&shape);
+
+    let start = usize::try_from(header.vox_offset).map_err(|_| ViewError::Parse("nifti vox_offset".to_string()))?;
+    let samples = bytes.get(start..).ok_or(ViewError::BufferSize { needed: start, got: bytes.len() })?;
+    let mut data = decode(samples,header.datatype,big_endian)?;
+    if data.len() < dims.numel() {
+        return Err(ViewError::BufferSize { needed: dims.numel(), got: data.len() });
+    }
+    data.truncate(dims.numel());
+    // a zero slope means the data isn't scaled
+    if header.scl_slope != 0. && (header.scl_slope,header.scl_inter) != (1.,0.) {
+        let (slope,inter) = (header.scl_slope as f32,header.scl_inter as f32);
+        data.iter_mut().for_each(|x| *x = *x * slope + inter);
+    }
+
+    let mut cfl_buffer = CflBuffer::new(data,dims);
+    let to_mm = match header.xyzt_units & 0x07 {
+        1 => 1000.,
+        3 => 0.001,
+        _ => 1.,
+    };
+    for (dim,size) in cfl_buffer.voxel_size.iter_mut().enumerate().take(n_dims as usize) {
+        let pixdim = header.pixdim[dim + 1].abs() as f32;
+        if pixdim > 0. && pixdim.is_finite() {
+            *size = if dim < 3 { pixdim * to_mm } else { pixdim };
+        }
+    }
+    cfl_buffer.geometry_known = true;
+    cfl_buffer.affine = affine(&header).map(|affine| affine.map(|row| row.map(|x| x as f32 * to_mm)));
+    Ok(cfl_buffer)
+}
+
+/// writes a cfl as nifti-1, or as nifti-2 if a dimension is too large for nifti-1. The file is gzipped if
+/// the path ends in .gz. Cfl dimensions past the seventh are folded into the fourth nifti dimension
+pub fn write_nifti(path:impl AsRef<Path>, cfl_buffer:&CflBuffer, components:NiftiComponents) -> Result<(),ViewError> {
+    let path = path.as_ref();
+    let volumes:Vec<(Option<&str>,Component)> = match components {
+        NiftiComponents::Magnitude => vec![(None,|x| x.norm())],
+        NiftiComponents::MagnitudePhase => vec![(Some("mag"),|x| x.norm()),(Some("phase"),|x| x.arg())],
+        NiftiComponents::RealImaginary => vec![(Some("real"),|x| x.re),(Some("imag"),|x| x.im)],
+        NiftiComponents::Complex => {
+            let bytes:Vec<u8> = cfl_buffer.data.iter().flat_map(|x| [x.re.to_le_bytes(),x.im.to_le_bytes()]).flatten().collect();
+            return write_volume(path,cfl_buffer,DT_COMPLEX64,&bytes)
+        }
+    };
+    for (suffix,component) in volumes {
+        let bytes:Vec<u8> = cfl_buffer.data.iter().flat_map(|x| component(*x).to_le_bytes()).collect();
+        let path = match suffix {
+            Some(suffix) => suffixed_path(path,suffix),
+            None => path.to_path_buf(),
+        };
+        write_volume(&path,cfl_buffer,DT_FLOAT32,&bytes)?;
+    }
+    Ok(())
+}
+
+/// adds a suffix to the file name before the .nii or .nii.gz extension
+pub fn suffixed_path(path:&Path, suffix:&str) -> PathBuf {
+    let name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+    let split = [".nii.gz",".nii"].iter()
+        .find(|ext| name.to_lowercase().ends_with(*ext))
+        .map_or(name.len(),|ext| name.len() - ext.len());
+    let (stem,ext) = name.split_at(split);
+    path.with_file_name(format!("{}_{}{}",stem,suffix,ext))
+}
+
+fn write_volume(path:&Path, cfl_buffer:&CflBuffer, datatype:i16, samples:&[u8]) -> Result<(),ViewError> {
+    let shape = cfl_buffer.dims.shape();
+    let used = (0..N_DIMS).rev().find(|&dim| shape[dim] > 1).map_or(3,|dim| (dim + 1).max(3));
+    let mut dim = [1i64;8];
+    let mut pixdim = [1f64;8];
+    if used <= NIFTI_DIMS {
+        dim[0] = used as i64;
+        for i in 0..used {
+            dim[i + 1] = shape[i] as i64;
+            pixdim[i + 1] = cfl_buffer.voxel_size[i] as f64;
+        }
+    }else {
+        // column-major, so the trailing dimensions fold into one without moving any samples
+        dim[0] = 4;
+        for i in 0..3 {
+            dim[i + 1] = shape[i] as i64;
+            pixdim[i + 1] = cfl_buffer.voxel_size[i] as f64;
+        }
+        dim[4] = shape[3..].iter().product::<usize>() as i64;
+    }
+
+    let mut header = Header {
+        dim,
+        datatype,
+        pixdim,
+        vox_offset: 0,
+        scl_slope: 1.,
+        scl_inter: 0.,
+        // mm and seconds
+        xyzt_units: 2 | 8,
+        qform_code: 0,
+        sform_code: 0,
+        quatern: [0.;3],
+        qoffset: [0.;3],
+        srow: [[0.;4];3],
+    };
+    if let Some(affine) = cfl_buffer.affine {
+        header.sform_code = 1;
+        header.srow = affine.map(|row| row.map(|x| x as f64));
+    }
+    let nifti2 = dim.iter().any(|&n| n > i16::MAX as i64);
+    // the header is followed by 4 bytes saying there are no extensions
+    header.vox_offset = if nifti2 { NIFTI2_HEADER as u64 + 4 } else { NIFTI1_HEADER as u64 + 4 };
+    let bytes = if nifti2 { nifti2_header(&header) } else { nifti1_header(&header) };
+
+    let writer = BufWriter::new(File::create(path)?);
+    let mut writer:Box<dyn Write> = if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("gz")) {
+        Box::new(GzEncoder::new(writer,Compression::default()))
+    }else {
+        Box::new(writer)
+    };
+    writer.write_all(&bytes)?;
+    writer.write_all(&[0;4])?;
+    writer.write_all(samples)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// reads the header, returning it with whether the file is big endian
+fn parse_header(bytes:&[u8]) -> Result<(Header,bool),ViewError> {
+    let sizeof_hdr = bytes.get(..4).ok_or(ViewError::BufferSize { needed: 4, got: bytes.len() })?;
+    let (size,big_endian) = match (i32::from_le_bytes(le(sizeof_hdr,false)),i32::from_le_bytes(le(sizeof_hdr,true))) {
+        (n,_) if n == NIFTI1_HEADER as i32 || n == NIFTI2_HEADER as i32 => (n as usize,false),
+        (_,n) if n == NIFTI1_HEADER as i32 || n == NIFTI2_HEADER as i32 => (n as usize,true),
+        _ => return Err(ViewError::Parse("not a nifti file".to_string())),
+    };
+    if bytes.len() < size {
+        return Err(ViewError::BufferSize { needed: size, got: bytes.len() });
+    }
+    let h = Fields { bytes, big_endian };
+    let header = if size == NIFTI1_HEADER {
+        if &bytes[344..347] != b"n+1" {
+            return Err(ViewError::Parse("only single file nifti-1 (.nii) is supported".to_string()));
+        }
+        Header {
+            dim: std::array::from_fn(|i| h.i16(40 + 2 * i) as i64),
+            datatype: h.i16(70),
+            pixdim: std::array::from_fn(|i| h.f32(76 + 4 * i) as f64),
+            vox_offset: h.f32(108).max(0.) as u64,
+            scl_slope: h.f32(112) as f64,
+            scl_inter: h.f32(116) as f64,
+            xyzt_units: bytes[123],
+            qform_code: h.i16(252) as i32,
+            sform_code: h.i16(254) as i32,
+            quatern: std::array::from_fn(|i| h.f32(256 + 4 * i) as f64),
+            qoffset: std::array::from_fn(|i| h.f32(268 + 4 * i) as f64),
+            srow: std::array::from_fn(|r| std::array::from_fn(|c| h.f32(280 + 16 * r + 4 * c) as f64)),
+        }
+    }else {
+        Header {
+            dim: std::array::from_fn(|i| h.i64(16 + 8 * i)),
+            datatype: h.i16(12),
+            pixdim: std::array::from_fn(|i| h.f64(104 + 8 * i)),
+            vox_offset: h.i64(168).max(0) as u64,
+            scl_slope: h.f64(176),
+            scl_inter: h.f64(184),
+            xyzt_units: h.i32(500) as u8,
+            qform_code: h.i32(344),
+            sform_code: h.i32(348),
+            quatern: std::array::from_fn(|i| h.f64(352 + 8 * i)),
+            qoffset: std::array::from_fn(|i| h.f64(376 + 8 * i)),
+            srow: std::array::from_fn(|r| std::array::from_fn(|c| h.f64(400 + 32 * r + 8 * c))),
+        }
+    };
+    Ok((header,big_endian))
+}
+
+/// the voxel to scanner transform, from the sform if set and the qform otherwise
+fn affine(header:&Header) -> Option<[[f64;4];3]> {
+    if header.sform_code > 0 {
+        return Some(header.srow)
+    }
+    if header.qform_code <= 0 {
+        return None
+    }
+    let [b,c,d] = header.quatern;
+    let a = (1. - (b * b + c * c + d * d)).max(0.).sqrt();
+    let rotation = [
+        [a * a + b * b - c * c - d * d, 2. * (b * c - a * d), 2. * (b * d + a * c)],
+        [2. * (b * c + a * d), a * a + c * c - b * b - d * d, 2. * (c * d - a * b)],
+        [2. * (b * d - a * c), 2. * (c * d + a * b), a * a + d * d - c * c - b * b],
+    ];
+    // pixdim[0] is the qfac, which flips the third axis when negative
+    let qfac = if header.pixdim[0] < 0. { -1. } else { 1. };
+    let scale = [header.pixdim[1],header.pixdim[2],header.pixdim[3] * qfac];
+    Some(std::array::from_fn(|r| [
+        rotation[r][0] * scale[0],
+        rotation[r][1] * scale[1],
+        rotation[r][2] * scale[2],
+        header.qoffset[r],
+    ]))
+}
+
+fn nifti1_header(header:&Header) -> Vec<u8> {
+    let mut bytes = vec![0u8;NIFTI1_HEADER];
+    let mut put = |offset:usize, value:&[u8]| bytes[offset..offset + value.len()].copy_from_slice(value);
+    put(0,&(NIFTI1_HEADER as i32).to_le_bytes());
+    for (i,&n) in header.dim.iter().enumerate() {
+        put(40 + 2 * i,&(n as i16).to_le_bytes());
+    }
+    put(70,&header.datatype.to_le_bytes());
+    put(72,&bitpix(header.datatype).to_le_bytes());
+    for (i,&size) in header.pixdim.iter().enumerate() {
+        put(76 + 4 * i,&(size as f32).to_le_bytes());
+    }
+    put(108,&(header.vox_offset as f32).to_le_bytes());
+    put(112,&(header.scl_slope as f32).to_le_bytes());
+    put(116,&(header.scl_inter as f32).to_le_bytes());
+    put(123,&[header.xyzt_units]);
+    put(252,&(header.qform_code as i16).to_le_bytes());
+    put(254,&(header.sform_code as i16).to_le_bytes());
+    for (r,row) in header.srow.iter().enumerate() {
+        for (c,&x) in row.iter().enumerate() {
+            put(280 + 16 * r + 4 * c,&(x as f32).to_le_bytes());
+        }
+    }
+    put(344,b"n+1\0");
+    bytes
+}
+
+fn nifti2_header(header:&Header) -> Vec<u8> {
+    let mut bytes = vec![0u8;NIFTI2_HEADER];
+    let mut put = |offset:usize, value:&[u8]| bytes[offset..offset + value.len()].copy_from_slice(value);
+    put(0,&(NIFTI2_HEADER as i32).to_le_bytes());
+    put(4,b"n+2\0\r\n\x1a\n");
+    put(12,&header.datatype.to_le_bytes());
+    put(14,&bitpix(header.datatype).to_le_bytes());
+    for (i,&n) in header.dim.iter().enumerate() {
+        put(16 + 8 * i,&n.to_le_bytes());
+    }
+    for (i,&size) in header.pixdim.iter().enumerate() {
+        put(104 + 8 * i,&size.to_le_bytes());
+    }
+    put(168,&(header.vox_offset as i64).to_le_bytes());
+    put(176,&header.scl_slope.to_le_bytes());
+    put(184,&header.scl_inter.to_le_bytes());
+    put(344,&header.qform_code.to_le_bytes());
+    put(348,&header.sform_code.to_le_bytes());
+    for (r,row) in header.srow.iter().enumerate() {
+        for (c,&x) in row.iter().enumerate() {
+            put(400 + 32 * r + 8 * c,&x.to_le_bytes());
+        }
+    }
+    put(500,&(header.xyzt_units as i32).to_le_bytes());
+    bytes
+}
+
+fn bitpix(datatype:i16) -> i16 {
+    match datatype {
+        DT_COMPLEX64 => 64,
+        _ => 32,
+    }
+}
+
+/// converts the samples of any real or complex nifti datatype
+fn decode(bytes:&[u8], datatype:i16, big_endian:bool) -> Result<Vec<Complex32>,ViewError> {
+    let (dtype,complex) = match datatype {
+        DT_UINT8 => (RawType::U8,false),
+        DT_INT8 => (RawType::I8,false),
+        DT_INT16 => (RawType::I16,false),
+        DT_UINT16 => (RawType::U16,false),
+        DT_INT32 => (RawType::I32,false),
+        DT_UINT32 => (RawType::U32,false),
+        DT_INT64 => (RawType::I64,false),
+        DT_UINT64 => (RawType::U64,false),
+        DT_FLOAT32 => (RawType::F32,false),
+        DT_FLOAT64 => (RawType::F64,false),
+        DT_COMPLEX64 => (RawType::F32,true),
+        DT_COMPLEX128 => (RawType::F64,true),
+        _ => return Err(ViewError::Parse(format!("unsupported nifti datatype {}",datatype))),
+    };
+    let values = dtype.decode(bytes,if big_endian { Endianness::Big } else { Endianness::Little });
+    let data = if complex {
+        values.chunks_exact(2).map(|x| Complex32::new(x[0],x[1])).collect()
+    }else {
+        values.into_iter().map(|x| Complex32::new(x,0.)).collect()
+    };
+    Ok(data)
+}
+
+/// reads header fields at byte offsets. The header length is checked before any field is read
+struct Fields<'a> {
+    bytes: &'a [u8],
+    big_endian: bool,
+}
+
+impl Fields<'_> {
+    fn i16(&self, offset:usize) -> i16 {
+        i16::from_le_bytes(le(&self.bytes[offset..],self.big_endian))
+    }
+    fn i32(&self, offset:usize) -> i32 {
+        i32::from_le_bytes(le(&self.bytes[offset..],self.big_endian))
+    }
+    fn i64(&self, offset:usize) -> i64 {
+        i64::from_le_bytes(le(&self.bytes[offset..],self.big_endian))
+    }
+    fn f32(&self, offset:usize) -> f32 {
+        f32::from_le_bytes(le(&self.bytes[offset..],self.big_endian))
+    }
+    fn f64(&self, offset:usize) -> f64 {
+        f64::from_le_bytes(le(&self.bytes[offset..],self.big_endian))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name:&str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("cfl_view_nifti_{}_{}",name,std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn ramp(shape:&[usize]) -> CflBuffer {
+        let dims = ArrayDim::from_shape(shape);
+        CflBuffer::new((0..dims.numel()).map(|i| Complex32::new(i as f32,1. - i as f32)).collect(),dims)
+    }
+
+    #[test]
+    fn nifti1_complex_round_trip() {
+        let dir = temp_dir("nifti1");
+        let mut cfl_buffer = ramp(&[4,3,2,2]);
+        cfl_buffer.voxel_size[..3].copy_from_slice(&[0.5,0.5,2.]);
+        cfl_buffer.affine = Some([[-0.5,0.,0.,10.],[0.,0.5,0.,-20.],[0.,0.,2.,30.]]);
+        for name in ["a.nii","a.nii.gz"] {
+            let path = dir.join(name);
+            write_nifti(&path,&cfl_buffer,NiftiComponents::Complex).unwrap();
+            let read = read_nifti(&path).unwrap();
+            assert_eq!(read.dims.shape(),cfl_buffer.dims.shape());
+            assert_eq!(read.data,cfl_buffer.data);
+            assert_eq!(&read.voxel_size[..3],&[0.5,0.5,2.]);
+            assert_eq!(read.affine,cfl_buffer.affine);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn nifti2_for_large_dims() {
+        let dir = temp_dir("nifti2");
+        let path = dir.join("long.nii");
+        let cfl_buffer = ramp(&[40000,2]);
+        write_nifti(&path,&cfl_buffer,NiftiComponents::Complex).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        assert_eq!(i32::from_le_bytes(bytes[..4].try_into().unwrap()),NIFTI2_HEADER as i32);
+        let read = read_nifti(&path).unwrap();
+        assert_eq!(read.dims.shape(),cfl_buffer.dims.shape());
+        assert_eq!(read.data,cfl_buffer.data);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn magnitude_phase_round_trip() {
+        let dir = temp_dir("mag_phase");
+        let path = dir.join("b.nii");
+        let cfl_buffer = ramp(&[3,2]);
+        write_nifti(&path,&cfl_buffer,NiftiComponents::MagnitudePhase).unwrap();
+        let magnitude = read_nifti(dir.join("b_mag.nii")).unwrap();
+        let phase = read_nifti(dir.join("b_phase.nii")).unwrap();
+        for ((x,m),p) in cfl_buffer.data.iter().zip(&magnitude.data).zip(&phase.data) {
+            assert_eq!(*m,Complex32::new(x.norm(),0.));
+            assert_eq!(*p,Complex32::new(x.arg(),0.));
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn qform_affine() {
+        // a quarter turn about z, 2x3x4 mm voxels and a negative qfac that flips z
+        let half = std::f64::consts::FRAC_1_SQRT_2;
+        let header = Header {
+            dim: [3,4,4,4,1,1,1,1],
+            datatype: DT_FLOAT32,
+            pixdim: [-1.,2.,3.,4.,1.,1.,1.,1.],
+            vox_offset: 352,
+            scl_slope: 1.,
+            scl_inter: 0.,
+            xyzt_units: 2,
+            qform_code: 1,
+            sform_code: 0,
+            quatern: [0.,0.,half],
+            qoffset: [10.,20.,30.],
+            srow: [[0.;4];3],
+        };
+        let expected = [[0.,-3.,0.,10.],[2.,0.,0.,20.],[0.,0.,-4.,30.]];
+        let affine = affine(&header).unwrap();
+        for (row,expected) in affine.iter().zip(expected) {
+            for (x,expected) in row.iter().zip(expected) {
+                assert!((x - expected).abs() < 1e-9,"{:?}",affine);
+            }
+        }
+    }
+}
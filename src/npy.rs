@@ -0,0 +1,242 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use array_lib::ArrayDim;
+use array_lib::cfl::num_complex::Complex32;
+use crate::cfl_buffer::CflBuffer;
+use crate::raw::{Endianness, RawType};
+use crate::slice::N_DIMS;
+use crate::ViewError;
+
+const NPY_MAGIC:&[u8] = b"\x93NUMPY";
+
+/// reads a .npy file. Axis i of the numpy array becomes cfl dimension i, so C ordered arrays are transposed
+/// in memory to the column-major cfl layout
+pub fn read_npy(path:impl AsRef<Path>) -> Result<CflBuffer,ViewError> {
+    let mut bytes = vec![];
+    File::open(path)?.read_to_end(&mut bytes)?;
+    parse_npy(&bytes)
+}
+
+/// reads every array in a .npz archive with its name, in the order they are stored
+pub fn read_npz(path:impl AsRef<Path>) -> Result<Vec<(String,CflBuffer)>,ViewError> {
+    let mut archive = zip::ZipArchive::new(BufReader::new(File::open(path)?)).map_err(|e| ViewError::Parse(e.to_string()))?;
+    let mut arrays = vec![];
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).map_err(|e| ViewError::Parse(e.to_string()))?;
+        let name = file.name().trim_end_matches(".npy").to_string();
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes)?;
+        arrays.push((name,parse_npy(&bytes)?));
+    }
+    Ok(arrays)
+}
+
+/// writes a cfl as a fortran ordered complex64 .npy, which numpy reads with the same axis order as the cfl.
+/// Trailing singleton dimensions are left off the shape
+pub fn write_npy(path:impl AsRef<Path>, cfl_buffer:&CflBuffer) -> Result<(),ViewError> {
+    let shape = cfl_buffer.dims.shape();
+    let n_dims = (0..N_DIMS).rev().find(|&dim| shape[dim] > 1).map_or(1,|dim| dim + 1);
+    let sizes:Vec<String> = shape[..n_dims].iter().map(|n| n.to_string()).collect();
+    // a one element tuple needs a trailing comma
+    let shape = if n_dims == 1 { format!("({},)",sizes[0]) } else { format!("({})",sizes.join(", ")) };
+    let mut header = format!("{{'descr': '<c8', 'fortran_order': True, 'shape': {}, }}",shape);
+    // the magic, version and header length take 10 bytes, and the header is padded so the data is aligned to 64
+    let padding = (64 - (10 + header.len() + 1) % 64) % 64;
+    header.push_str(&" ".repeat(padding));
+    header.push('\n');
+    let header_len = u16::try_from(header.len()).map_err(|_| ViewError::Encode("npy header is too long".to_string()))?;
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(NPY_MAGIC)?;
+    writer.write_all(&[1,0])?;
+    writer.write_all(&header_len.to_le_bytes())?;
+    writer.write_all(header.as_bytes())?;
+    for x in &cfl_buffer.data {
+        writer.write_all(&x.re.to_le_bytes())?;
+        writer.write_all(&x.im.to_le_bytes())?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// parses the contents of a .npy file
+fn parse_npy(bytes:&[u8]) -> Result<CflBuffer,ViewError> {
+    if !bytes.starts_with(NPY_MAGIC) || bytes.len() < 10 {
+        return Err(ViewError::Parse("not a npy file".to_string()));
+    }
+    // version 1 has a 2 byte header length and later versions a 4 byte one
+    let (header_len,header_start) = match bytes[6] {
+        1 => (u16::from_le_bytes([bytes[8],bytes[9]]) as usize,10),
+        _ if bytes.len() >= 12 => (u32::from_le_bytes([bytes[8],bytes[9],bytes[10],bytes[11]]) as usize,12),
+        _ => return Err(ViewError::Parse("truncated npy header".to_string())),
+    };
+    let data_start = header_start + header_len;
+    let header = bytes.get(header_start..data_start).ok_or(ViewError::BufferSize { needed: data_start, got: bytes.len() })?;
+    let header = String::from_utf8_lossy(header);
+
+    let descr = header_value(&header,"descr")?.trim_matches(|c| c == '\'' || c == '"');
+    let fortran_order = header_value(&header,"fortran_order")? == "True";
+    let shape:Vec<usize> = header_value(&header,"shape")?
+        .trim_matches(|c| c == '(' || c == ')')
+        .split(',')
+        .filter(|size| !size.trim().is_empty())
+        .map(|size| size.trim().parse().map_err(|_| ViewError::Parse(size.to_string())))
+        .collect::<Result<_,_>>()?;
+    if shape.len() > N_DIMS {
+        return Err(ViewError::BadIndex(shape.len()));
+    }
+
+    let (dtype,complex,endianness) = parse_descr(descr)?;
+    let values = dtype.decode(&bytes[data_start..],endianness);
+    let samples:Vec<Complex32> = if complex {
+        values.chunks_exact(2).map(|x| Complex32::new(x[0],x[1])).collect()
+    }else {
+        values.into_iter().map(|x| Complex32::new(x,0.)).collect()
+    };
+
+    let dims = ArrayDim::from_shape(&shape);
+    if samples.len() < dims.numel() {
+        return Err(ViewError::BufferSize { needed: dims.numel(), got: samples.len() });
+    }
+    if fortran_order || shape.len() < 2 {
+        return Ok(CflBuffer::new(samples[..dims.numel()].to_vec(),dims))
+    }
+    // walk the cfl in column-major order, keeping the address of the same element in the C ordered samples
+    let mut c_strides = vec![1;shape.len()];
+    for axis in (0..shape.len() - 1).rev() {
+        c_strides[axis] = c_strides[axis + 1] * shape[axis + 1];
+    }
+    let mut idx = vec![0;shape.len()];
+    let mut addr = 0;
+    let data = (0..dims.numel()).map(|_|{
+        let x = samples[addr];
+        for axis in 0..shape.len() {
+            idx[axis] += 1;
+            addr += c_strides[axis];
+            if idx[axis] < shape[axis] {
+                break
+            }
+            addr -= c_strides[axis] * shape[axis];
+            idx[axis] = 0;
+        }
+        x
+    }).collect();
+    Ok(CflBuffer::new(data,dims))
+}
+
+/// the text of a value in the header dictionary, up to the next top level comma or closing brace
+fn header_value<'a>(header:&'a str, key:&str) -> Result<&'a str,ViewError> {
+    let missing = || ViewError::Parse(format!("npy header without {}",key));
+    let start = ["'","\""].iter()
+        .find_map(|quote| header.find(&format!("{}{}{}",quote,key,quote)))
+        .ok_or_else(missing)?;
+    let rest = &header[start + key.len() + 2..];
+    let rest = rest.trim_start().strip_prefix(':').ok_or_else(missing)?.trim_start();
+    let end = if rest.starts_with('(') {
+        rest.find(')').map(|i| i + 1)
+    }else {
+        rest.find([',','}'])
+    };
+    Ok(rest[..end.unwrap_or(rest.len())].trim())
+}
+
+/// the scalar type of a numpy dtype string such as '<c8', with whether the samples are complex
+fn parse_descr(descr:&str) -> Result<(RawType,bool,Endianness),ViewError> {
+    let unsupported = || ViewError::Parse(format!("unsupported npy dtype {}",descr));
+    let mut chars = descr.chars();
+    let endianness = match chars.next() {
+        Some('>') => Endianness::Big,
+        Some('<' | '|' | '=') => Endianness::Little,
+        _ => return Err(unsupported()),
+    };
+    let kind = chars.next().ok_or_else(unsupported)?;
+    let size:usize = chars.as_str().parse().map_err(|_| unsupported())?;
+    let dtype = match (kind,size) {
+        ('c',8) => (RawType::F32,true),
+        ('c',16) => (RawType::F64,true),
+        ('f',4) => (RawType::F32,false),
+        ('f',8) => (RawType::F64,false),
+        ('i',1) => (RawType::I8,false),
+        ('i',2) => (RawType::I16,false),
+        ('i',4) => (RawType::I32,false),
+        ('i',8) => (RawType::I64,false),
+        ('u' | 'b',1) => (RawType::U8,false),
+        ('u',2) => (RawType::U16,false),
+        ('u',4) => (RawType::U32,false),
+        ('u',8) => (RawType::U64,false),
+        _ => return Err(unsupported()),
+    };
+    Ok((dtype.0,dtype.1,endianness))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a .npy file of the given version, with the header padded like numpy does
+    fn npy(version:u8, header:&str, data:&[u8]) -> Vec<u8> {
+        let mut bytes = NPY_MAGIC.to_vec();
+        bytes.extend([version,0]);
+        match version {
+            1 => bytes.extend((header.len() as u16).to_le_bytes()),
+            _ => bytes.extend((header.len() as u32).to_le_bytes()),
+        }
+        bytes.extend(header.as_bytes());
+        bytes.extend(data);
+        bytes
+    }
+
+    #[test]
+    fn write_read_round_trip() {
+        let dims = ArrayDim::from_shape(&[3,2,4]);
+        let data:Vec<Complex32> = (0..dims.numel()).map(|i| Complex32::new(i as f32,-(i as f32))).collect();
+        let path = std::env::temp_dir().join(format!("cfl_view_npy_{}.npy",std::process::id()));
+        write_npy(&path,&CflBuffer::new(data.clone(),dims)).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        let header_len = u16::from_le_bytes([bytes[8],bytes[9]]) as usize;
+        assert_eq!((10 + header_len) % 64,0);
+        assert!(String::from_utf8_lossy(&bytes[10..10 + header_len]).contains("'shape': (3, 2, 4)"));
+        let cfl_buffer = read_npy(&path).unwrap();
+        assert_eq!(cfl_buffer.dims.shape()[..3],[3,2,4]);
+        assert_eq!(cfl_buffer.data,data);
+
+        // a vector is written with a one element tuple
+        let vector = CflBuffer::new(data[..5].to_vec(),ArrayDim::from_shape(&[5]));
+        write_npy(&path,&vector).unwrap();
+        assert!(String::from_utf8_lossy(&std::fs::read(&path).unwrap()).contains("'shape': (5,)"));
+        assert_eq!(read_npy(&path).unwrap().data,vector.data);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn c_and_fortran_order() {
+        // element [i,j,k] of a 2x3x4 array holds 100i + 10j + k
+        let value = |i:usize, j:usize, k:usize| (100 * i + 10 * j + k) as i32;
+        let c_order:Vec<u8> = (0..24).flat_map(|n| value(n / 12,n / 4 % 3,n % 4).to_le_bytes()).collect();
+        let fortran_order:Vec<u8> = (0..24).flat_map(|n| value(n % 2,n / 2 % 3,n / 6).to_le_bytes()).collect();
+        let c = parse_npy(&npy(1,"{'descr': '<i4', 'fortran_order': False, 'shape': (2, 3, 4), }\n",&c_order)).unwrap();
+        let fortran = parse_npy(&npy(1,"{'descr': '<i4', 'fortran_order': True, 'shape': (2, 3, 4), }\n",&fortran_order)).unwrap();
+        assert_eq!(c.dims.shape()[..3],[2,3,4]);
+        assert_eq!(c.data,fortran.data);
+        let mut idx = [0;N_DIMS];
+        idx[..3].copy_from_slice(&[1,2,3]);
+        assert_eq!(c.get(&idx),Some(Complex32::new(123.,0.)));
+    }
+
+    #[test]
+    fn one_element_shape() {
+        let data:Vec<u8> = [1.5f64,-2.].iter().flat_map(|x| x.to_le_bytes()).collect();
+        let cfl_buffer = parse_npy(&npy(1,"{'descr': '<f8', 'fortran_order': False, 'shape': (2,), }\n",&data)).unwrap();
+        assert_eq!(cfl_buffer.dims.shape()[..2],[2,1]);
+        assert_eq!(cfl_buffer.data,vec![Complex32::new(1.5,0.),Complex32::new(-2.,0.)]);
+    }
+
+    #[test]
+    fn version_2_header() {
+        let data:Vec<u8> = [1i16,-300].iter().flat_map(|x| x.to_be_bytes()).collect();
+        let cfl_buffer = parse_npy(&npy(2,"{\"descr\": \">i2\", \"fortran_order\": False, \"shape\": (2,)}\n",&data)).unwrap();
+        assert_eq!(cfl_buffer.data,vec![Complex32::new(1.,0.),Complex32::new(-300.,0.)]);
+        assert!(parse_npy(&npy(2,"{'descr': '<f4', 'fortran_order': False, 'shape': (4,), }\n",&[0;12])).is_err());
+    }
+}
@@ -0,0 +1,103 @@
+use array_lib::ArrayDim;
+use array_lib::cfl::num_complex::Complex32;
+use crate::cfl_buffer::CflBuffer;
+use crate::slice::N_DIMS;
+use crate::ViewError;
+
+/// samples a slice along a line between two points in continuous pixel coordinates, where pixel (x,y) covers
+/// [x,x+1) x [y,y+1). Samples are spaced one pixel apart with bilinear interpolation. Returns the distance
+/// of each sample from the start of the line in pixels with its value.
+pub fn line_profile(data:&[Complex32], dims:&ArrayDim, from:[f32;2], to:[f32;2]) -> Vec<(f32,Complex32)> {
+    let width = dims.shape()[0];
+    let height = dims.shape()[1];
+    if width == 0 || height == 0 {
+        return vec![]
+    }
+    let dx = to[0] - from[0];
+    let dy = to[1] - from[1];
+    let length = (dx * dx + dy * dy).sqrt();
+    let n = length.ceil() as usize + 1;
+    (0..n).map(|i|{
+        let t = if n > 1 { i as f32 / (n - 1) as f32 } else { 0. };
+        // shift to pixel centers
+        let x = from[0] + t * dx - 0.5;
+        let y = from[1] + t * dy - 0.5;
+        (t * length,bilinear(data,width,height,x,y))
+    }).collect()
+}
+
+/// interpolates a slice at (x,y) in pixel center coordinates, clamping at the edges
+fn bilinear(data:&[Complex32], width:usize, height:usize, x:f32, y:f32) -> Complex32 {
+    let x = x.clamp(0.,(width - 1) as f32);
+    let y = y.clamp(0.,(height - 1) as f32);
+    let x0 = x.floor() as usize;
+    let y0 = y.floor() as usize;
+    let x1 = (x0 + 1).min(width - 1);
+    let y1 = (y0 + 1).min(height - 1);
+    let wx = x - x0 as f32;
+    let wy = y - y0 as f32;
+    let top = data[y0 * width + x0] * (1. - wx) + data[y0 * width + x1] * wx;
+    let bottom = data[y1 * width + x0] * (1. - wx) + data[y1 * width + x1] * wx;
+    top * (1. - wy) + bottom * wy
+}
+
+/// the samples along one cfl dimension through a full index, e.g. signal vs. echo time or vs. coil
+pub fn dim_profile(cfl_buffer:&CflBuffer, idx:&[usize;N_DIMS], dim:usize) -> Result<Vec<Complex32>,ViewError> {
+    if dim >= N_DIMS {
+        return Err(ViewError::BadIndex(dim));
+    }
+    let n = cfl_buffer.dims.shape()[dim];
+    let mut idx = *idx;
+    (0..n).map(|i|{
+        idx[dim] = i;
+        cfl_buffer.get(&idx).ok_or(ViewError::IndexOutOfBounds { dim, index: i, size: n })
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a 4x3 slice where pixel (x,y) holds x + 10y
+    fn ramp() -> (Vec<Complex32>,ArrayDim) {
+        ((0..12).map(|i| Complex32::new((i % 4 + 10 * (i / 4)) as f32,0.)).collect(),ArrayDim::from_shape(&[4,3]))
+    }
+
+    fn values(profile:&[(f32,Complex32)]) -> Vec<f32> {
+        profile.iter().map(|(_,x)| x.re).collect()
+    }
+
+    #[test]
+    fn along_a_row() {
+        let (data,dims) = ramp();
+        let profile = line_profile(&data,&dims,[0.5,1.5],[3.5,1.5]);
+        assert_eq!(profile.iter().map(|(d,_)| *d).collect::<Vec<_>>(),[0.,1.,2.,3.]);
+        assert_eq!(values(&profile),[10.,11.,12.,13.]);
+    }
+
+    #[test]
+    fn interpolates_between_centers() {
+        let (data,dims) = ramp();
+        let profile = line_profile(&data,&dims,[1.5,0.5],[1.5,2.]);
+        assert_eq!(profile.len(),3);
+        assert_eq!(profile[1].0,0.75);
+        assert_eq!(values(&profile),[1.,8.5,16.]);
+        // clamped at the edges
+        let profile = line_profile(&data,&dims,[0.,0.5],[4.,0.5]);
+        assert_eq!(values(&profile),[0.,0.5,1.5,2.5,3.]);
+        assert!(line_profile(&[],&ArrayDim::from_shape(&[0,3]),[0.,0.],[1.,1.]).is_empty());
+    }
+
+    #[test]
+    fn along_a_dim() {
+        let data = (0..6).map(|i| Complex32::new(i as f32,0.)).collect();
+        let cfl_buffer = CflBuffer::new(data,ArrayDim::from_shape(&[2,1,1,3]));
+        let mut idx = [0;N_DIMS];
+        idx[0] = 1;
+        let profile = dim_profile(&cfl_buffer,&idx,3).unwrap();
+        assert_eq!(profile.iter().map(|x| x.re).collect::<Vec<_>>(),[1.,3.,5.]);
+        assert!(matches!(dim_profile(&cfl_buffer,&idx,N_DIMS),Err(ViewError::BadIndex(N_DIMS))));
+        idx[0] = 2;
+        assert!(dim_profile(&cfl_buffer,&idx,3).is_err());
+    }
+}
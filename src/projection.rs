@@ -0,0 +1,153 @@
+use std::fmt::{Display, Formatter};
+use array_lib::ArrayDim;
+use array_lib::cfl::num_complex::Complex32;
+use rayon::prelude::*;
+use crate::cfl_buffer::CflBuffer;
+use crate::slice::N_DIMS;
+use crate::ViewError;
+
+/// how samples along the projected dimension are combined
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProjectionMode {
+    /// sample with the largest magnitude
+    Max,
+    /// sample with the smallest magnitude
+    Min,
+    /// mean of the magnitudes, so samples of opposing phase don't cancel
+    Mean,
+    /// root sum of squares of the magnitudes, the same as `bart rss`
+    SumOfSquares,
+}
+
+impl ProjectionMode {
+    pub const ALL: [ProjectionMode;4] = [
+        ProjectionMode::Max,
+        ProjectionMode::Min,
+        ProjectionMode::Mean,
+        ProjectionMode::SumOfSquares,
+    ];
+}
+
+impl Display for ProjectionMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProjectionMode::Max => write!(f, "MIP"),
+            ProjectionMode::Min => write!(f, "minIP"),
+            ProjectionMode::Mean => write!(f, "mean"),
+            ProjectionMode::SumOfSquares => write!(f, "sum of squares"),
+        }
+    }
+}
+
+/// collapses one dimension of a cfl array
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Projection {
+    pub mode: ProjectionMode,
+    /// the cfl dimension to collapse
+    pub dim: usize,
+}
+
+impl Projection {
+
+    pub fn new(mode:ProjectionMode, dim:usize) -> Projection {
+        Projection { mode, dim }
+    }
+
+    /// returns a new buffer with the projected dimension reduced to a size of 1
+    pub fn project(&self, cfl_buffer:&CflBuffer) -> Result<CflBuffer,ViewError> {
+        if self.dim >= N_DIMS {
+            return Err(ViewError::BadIndex(self.dim));
+        }
+
+        let mut shape = [1usize;N_DIMS];
+        for (s,&d) in shape.iter_mut().zip(cfl_buffer.dims.shape().iter()) {
+            *s = d;
+        }
+        let n = shape[self.dim];
+        shape[self.dim] = 1;
+        let dims = ArrayDim::from_shape(&shape);
+
+        // samples along the projected dim are separated by its stride. Everything before it in memory is
+        // contiguous and everything after it is offset by the full extent of the dim
+        let stride = cfl_buffer.dims.strides()[self.dim];
+        let mode = self.mode;
+
+        let mut data = vec![Complex32::ZERO;dims.numel()];
+        data.par_iter_mut().enumerate().for_each(|(i,out)|{
+            let base = (i % stride) + (i / stride) * stride * n;
+            let samples = (0..n).map(|k| cfl_buffer.data[base + k * stride]);
+            *out = reduce(mode,samples,n);
+        });
+
+        Ok(CflBuffer {
+            data,
+            dims,
+            voxel_size: cfl_buffer.voxel_size,
+            geometry_known: cfl_buffer.geometry_known,
+            affine: cfl_buffer.affine,
+        })
+    }
+
+}
+
+fn reduce(mode:ProjectionMode, samples:impl Iterator<Item=Complex32>, n:usize) -> Complex32 {
+    match mode {
+        ProjectionMode::Max => samples.max_by(|a,b| a.norm_sqr().total_cmp(&b.norm_sqr())).unwrap_or(Complex32::ZERO),
+        ProjectionMode::Min => samples.min_by(|a,b| a.norm_sqr().total_cmp(&b.norm_sqr())).unwrap_or(Complex32::ZERO),
+        ProjectionMode::Mean => {
+            let sum = samples.map(|x| x.norm()).sum::<f32>();
+            Complex32::new(if n > 0 { sum / n as f32 } else { sum },0.)
+        }
+        ProjectionMode::SumOfSquares => {
+            let sos = samples.map(|x| x.norm_sqr()).sum::<f32>().sqrt();
+            Complex32::new(sos,0.)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a 2x3x2 array where sample n holds n + 1 with alternating sign, so neighbors along dim 0 have
+    /// opposing phase
+    fn alternating() -> CflBuffer {
+        let data = (0..12).map(|n| Complex32::new(((n + 1) * if n % 2 == 0 { 1 } else { -1 }) as f32,0.)).collect();
+        CflBuffer::new(data,ArrayDim::from_shape(&[2,3,2]))
+    }
+
+    fn project(mode:ProjectionMode, dim:usize) -> Vec<f32> {
+        let projected = Projection::new(mode,dim).project(&alternating()).unwrap();
+        let mut shape = [2,3,2];
+        shape[dim] = 1;
+        assert_eq!(projected.dims.shape()[..3],shape);
+        projected.data.iter().map(|x| x.re).collect()
+    }
+
+    #[test]
+    fn along_each_dim() {
+        assert_eq!(project(ProjectionMode::Max,0),[-2.,-4.,-6.,-8.,-10.,-12.]);
+        assert_eq!(project(ProjectionMode::Min,0),[1.,3.,5.,7.,9.,11.]);
+        assert_eq!(project(ProjectionMode::Mean,0),[1.5,3.5,5.5,7.5,9.5,11.5]);
+        assert_eq!(project(ProjectionMode::Max,1),[5.,-6.,11.,-12.]);
+        assert_eq!(project(ProjectionMode::Mean,1),[3.,4.,9.,10.]);
+        assert_eq!(project(ProjectionMode::Max,2),[7.,-8.,9.,-10.,11.,-12.]);
+        assert_eq!(project(ProjectionMode::Min,2),[1.,-2.,3.,-4.,5.,-6.]);
+        assert_eq!(project(ProjectionMode::Mean,2),[4.,5.,6.,7.,8.,9.]);
+        let sos = project(ProjectionMode::SumOfSquares,2);
+        assert!((sos[0] - 50f32.sqrt()).abs() < 1e-5);
+        assert!((sos[5] - 180f32.sqrt()).abs() < 1e-5);
+    }
+
+    #[test]
+    fn keeps_geometry() {
+        let mut cfl_buffer = alternating();
+        cfl_buffer.voxel_size[1] = 2.;
+        cfl_buffer.geometry_known = true;
+        let projected = Projection::new(ProjectionMode::Max,3).project(&cfl_buffer).unwrap();
+        assert_eq!(projected.data,cfl_buffer.data);
+        assert_eq!(projected.voxel_size,cfl_buffer.voxel_size);
+        assert!(projected.geometry_known);
+        assert!(matches!(Projection::new(ProjectionMode::Max,N_DIMS).project(&cfl_buffer),Err(ViewError::BadIndex(N_DIMS))));
+    }
+}
@@ -0,0 +1,299 @@
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use array_lib::ArrayDim;
+use array_lib::cfl::num_complex::Complex32;
+use crate::cfl_buffer::{CflBuffer, DEFAULT_DIMS};
+use crate::slice::N_DIMS;
+use crate::ViewError;
+
+/// type of the scalars in a raw file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RawType {
+    U8,
+    I8,
+    U16,
+    I16,
+    U32,
+    I32,
+    I64,
+    U64,
+    F32,
+    F64,
+}
+
+impl RawType {
+    pub const ALL: [RawType;10] = [
+        RawType::U8,
+        RawType::I8,
+        RawType::U16,
+        RawType::I16,
+        RawType::U32,
+        RawType::I32,
+        RawType::I64,
+        RawType::U64,
+        RawType::F32,
+        RawType::F64,
+    ];
+
+    /// bytes per scalar
+    pub fn size(&self) -> usize {
+        match self {
+            RawType::U8 | RawType::I8 => 1,
+            RawType::U16 | RawType::I16 => 2,
+            RawType::U32 | RawType::I32 | RawType::F32 => 4,
+            RawType::I64 | RawType::U64 | RawType::F64 => 8,
+        }
+    }
+
+    /// converts packed scalars to f32. Trailing bytes that don't make up a whole scalar are ignored
+    pub fn decode(&self, bytes:&[u8], endianness:Endianness) -> Vec<f32> {
+        let big_endian = endianness == Endianness::Big;
+        let scalars = bytes.chunks_exact(self.size());
+        match self {
+            RawType::U8 => scalars.map(|b| b[0] as f32).collect(),
+            RawType::I8 => scalars.map(|b| b[0] as i8 as f32).collect(),
+            RawType::U16 => scalars.map(|b| u16::from_le_bytes(le(b,big_endian)) as f32).collect(),
+            RawType::I16 => scalars.map(|b| i16::from_le_bytes(le(b,big_endian)) as f32).collect(),
+            RawType::U32 => scalars.map(|b| u32::from_le_bytes(le(b,big_endian)) as f32).collect(),
+            RawType::I32 => scalars.map(|b| i32::from_le_bytes(le(b,big_endian)) as f32).collect(),
+            RawType::I64 => scalars.map(|b| i64::from_le_bytes(le(b,big_endian)) as f32).collect(),
+            RawType::U64 => scalars.map(|b| u64::from_le_bytes(le(b,big_endian)) as f32).collect(),
+            RawType::F32 => scalars.map(|b| f32::from_le_bytes(le(b,big_endian))).collect(),
+            RawType::F64 => scalars.map(|b| f64::from_le_bytes(le(b,big_endian)) as f32).collect(),
+        }
+    }
+
+    /// parses the names shown by Display, such as i16 or f32
+    pub fn parse(name:&str) -> Result<RawType,ViewError> {
+        RawType::ALL.into_iter()
+            .find(|dtype| dtype.to_string() == name.trim().to_lowercase())
+            .ok_or(ViewError::Parse(name.to_string()))
+    }
+}
+
+impl Display for RawType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RawType::U8 => write!(f, "u8"),
+            RawType::I8 => write!(f, "i8"),
+            RawType::U16 => write!(f, "u16"),
+            RawType::I16 => write!(f, "i16"),
+            RawType::U32 => write!(f, "u32"),
+            RawType::I32 => write!(f, "i32"),
+            RawType::I64 => write!(f, "i64"),
+            RawType::U64 => write!(f, "u64"),
+            RawType::F32 => write!(f, "f32"),
+            RawType::F64 => write!(f, "f64"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endianness {
+    Little,
+    Big,
+}
+
+impl Endianness {
+    pub const ALL: [Endianness;2] = [Endianness::Little, Endianness::Big];
+}
+
+impl Display for Endianness {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Endianness::Little => write!(f, "little endian"),
+            Endianness::Big => write!(f, "big endian"),
+        }
+    }
+}
+
+/// how the real and imaginary parts of the samples are laid out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RawLayout {
+    /// real samples only
+    Real,
+    /// real and imaginary parts alternate, like a cfl or complex64
+    Interleaved,
+    /// every real part, followed by every imaginary part
+    Split,
+}
+
+impl RawLayout {
+    pub const ALL: [RawLayout;3] = [RawLayout::Real, RawLayout::Interleaved, RawLayout::Split];
+
+    /// scalars stored per sample
+    pub fn scalars(&self) -> usize {
+        match self {
+            RawLayout::Real => 1,
+            RawLayout::Interleaved | RawLayout::Split => 2,
+        }
+    }
+
+    pub fn parse(name:&str) -> Result<RawLayout,ViewError> {
+        RawLayout::ALL.into_iter()
+            .find(|layout| layout.to_string() == name.trim().to_lowercase())
+            .ok_or(ViewError::Parse(name.to_string()))
+    }
+}
+
+impl Display for RawLayout {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RawLayout::Real => write!(f, "real"),
+            RawLayout::Interleaved => write!(f, "interleaved"),
+            RawLayout::Split => write!(f, "split"),
+        }
+    }
+}
+
+/// describes a headerless array on disk. Samples are column-major like a cfl, so the first dimension
+/// varies fastest
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RawFormat {
+    pub shape: [usize;N_DIMS],
+    pub dtype: RawType,
+    pub endianness: Endianness,
+    /// bytes to skip at the start of the file
+    pub offset: u64,
+    pub layout: RawLayout,
+}
+
+impl Default for RawFormat {
+    /// a 128x128 float32 image
+    fn default() -> RawFormat {
+        let mut shape = [1;N_DIMS];
+        shape[0] = DEFAULT_DIMS;
+        shape[1] = DEFAULT_DIMS;
+        RawFormat {
+            shape,
+            dtype: RawType::F32,
+            endianness: Endianness::Little,
+            offset: 0,
+            layout: RawLayout::Real,
+        }
+    }
+}
+
+impl RawFormat {
+
+    /// bytes of sample data the format describes, not counting the offset. Shapes too large to address are
+    /// an error
+    pub fn data_size(&self) -> Result<usize,ViewError> {
+        self.shape.iter().chain([self.layout.scalars(),self.dtype.size()].iter())
+            .try_fold(1usize,|size,&n| size.checked_mul(n))
+            .ok_or(ViewError::Parse(format!("{:?} {} samples are too large",self.shape,self.dtype)))
+    }
+
+    /// reads the array from a file. Files may be longer than the format, and anything past the data is
+    /// ignored
+    pub fn read(&self, path:impl AsRef<Path>) -> Result<CflBuffer,ViewError> {
+        let mut file = File::open(path)?;
+        let data_size = self.data_size()?;
+        let needed = usize::try_from(self.offset).ok()
+            .and_then(|offset| offset.checked_add(data_size))
+            .ok_or(ViewError::Parse(format!("offset {} is too large",self.offset)))?;
+        let got = usize::try_from(file.metadata()?.len()).unwrap_or(usize::MAX);
+        if got < needed {
+            return Err(ViewError::BufferSize { needed, got });
+        }
+        file.seek(SeekFrom::Start(self.offset))?;
+        let mut bytes = vec![0u8;data_size];
+        file.read_exact(&mut bytes)?;
+        let values = self.dtype.decode(&bytes,self.endianness);
+        let data = match self.layout {
+            RawLayout::Real => values.into_iter().map(|x| Complex32::new(x,0.)).collect(),
+            RawLayout::Interleaved => values.chunks_exact(2).map(|x| Complex32::new(x[0],x[1])).collect(),
+            RawLayout::Split => {
+                let (re,im) = values.split_at(values.len() / 2);
+                re.iter().zip(im).map(|(&re,&im)| Complex32::new(re,im)).collect()
+            }
+        };
+        Ok(CflBuffer::new(data,ArrayDim::from_shape(&self.shape)))
+    }
+
+}
+
+/// parses an array shape from a list of sizes, such as the comma separated list given on the command line.
+/// Missing dimensions are 1
+pub fn parse_shape<'a>(values:impl IntoIterator<Item=&'a str>) -> Result<[usize;N_DIMS],ViewError> {
+    let mut shape = [1;N_DIMS];
+    for (i,value) in values.into_iter().enumerate() {
+        if i >= N_DIMS {
+            return Err(ViewError::BadIndex(i));
+        }
+        shape[i] = match value.trim().parse() {
+            Ok(size) if size > 0 => size,
+            _ => return Err(ViewError::Parse(value.to_string())),
+        };
+    }
+    Ok(shape)
+}
+
+/// the first N bytes in little endian order
+pub(crate) fn le<const N:usize>(bytes:&[u8], big_endian:bool) -> [u8;N] {
+    let mut out = [0;N];
+    out.copy_from_slice(&bytes[..N]);
+    if big_endian {
+        out.reverse();
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn write_temp(name:&str, bytes:&[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("cfl_view_raw_{}_{}.raw",name,std::process::id()));
+        std::fs::write(&path,bytes).unwrap();
+        path
+    }
+
+    fn format(shape:&[usize], dtype:RawType, base:RawFormat) -> RawFormat {
+        let mut format = RawFormat { dtype, ..base };
+        format.shape[..shape.len()].copy_from_slice(shape);
+        format.shape[shape.len()..].fill(1);
+        format
+    }
+
+    #[test]
+    fn interleaved_and_split() {
+        let values:Vec<u8> = [1i16,2,3,4,5,6].iter().flat_map(|x| x.to_le_bytes()).collect();
+        let path = write_temp("layouts",&[vec![0xff;4],values].concat());
+        let base = RawFormat { offset: 4, ..RawFormat::default() };
+        let interleaved = format(&[3],RawType::I16,RawFormat { layout: RawLayout::Interleaved, ..base }).read(&path).unwrap();
+        assert_eq!(interleaved.data,vec![Complex32::new(1.,2.),Complex32::new(3.,4.),Complex32::new(5.,6.)]);
+        let split = format(&[3],RawType::I16,RawFormat { layout: RawLayout::Split, ..base }).read(&path).unwrap();
+        assert_eq!(split.data,vec![Complex32::new(1.,4.),Complex32::new(2.,5.),Complex32::new(3.,6.)]);
+        let real = format(&[2,3],RawType::I16,base).read(&path).unwrap();
+        assert_eq!(real.dims.shape()[..2],[2,3]);
+        assert_eq!(real.data[5],Complex32::new(6.,0.));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn big_endian() {
+        let path = write_temp("big_endian",&[[1u16.to_be_bytes(),258u16.to_be_bytes()].concat(),(-1.5f64).to_be_bytes().to_vec()].concat());
+        let base = RawFormat { endianness: Endianness::Big, ..RawFormat::default() };
+        let u16s = format(&[2],RawType::U16,base).read(&path).unwrap();
+        assert_eq!(u16s.data,vec![Complex32::new(1.,0.),Complex32::new(258.,0.)]);
+        let f64s = format(&[1],RawType::F64,RawFormat { offset: 4, ..base }).read(&path).unwrap();
+        assert_eq!(f64s.data,vec![Complex32::new(-1.5,0.)]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn rejects_short_and_oversized() {
+        let path = write_temp("short",&[0;12]);
+        let base = RawFormat::default();
+        assert!(matches!(format(&[4],RawType::F32,base).read(&path),Err(ViewError::BufferSize { needed: 16, got: 12 })));
+        let huge = format(&[usize::MAX / 2,4],RawType::F64,base);
+        assert!(matches!(huge.data_size(),Err(ViewError::Parse(_))));
+        assert!(huge.read(&path).is_err());
+        assert!(format(&[1],RawType::U8,RawFormat { offset: u64::MAX, ..base }).read(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,233 @@
+use array_lib::ArrayDim;
+use array_lib::cfl::num_complex::Complex32;
+use rayon::prelude::*;
+use crate::cfl_buffer::CflBuffer;
+use crate::slice::N_DIMS;
+use crate::ViewError;
+
+/// an arbitrary plane through a 3-D volume of a cfl array
+#[derive(Debug, Clone, Copy)]
+pub struct ObliquePlane {
+    /// the cfl dimensions making up the (x,y,z) axes of the volume
+    pub volume_dims: [usize;3],
+    /// normal of the plane in physical coordinates. Doesn't need to be normalized
+    pub normal: [f32;3],
+    /// distance of the plane from the volume center along the normal in mm
+    pub offset: f32,
+    /// size of a voxel along the (x,y,z) axes of the volume in mm
+    pub voxel_size: [f32;3],
+    /// index into the dimensions outside the volume
+    pub fixed: [usize;N_DIMS],
+}
+
+impl Default for ObliquePlane {
+    fn default() -> ObliquePlane {
+        ObliquePlane {
+            volume_dims: [0,1,2],
+            normal: [0.,0.,1.],
+            offset: 0.,
+            voxel_size: [1.;3],
+            fixed: [0;N_DIMS],
+        }
+    }
+}
+
+impl ObliquePlane {
+
+    /// plane with a normal given by polar angle theta from the z-axis and azimuth phi from the x-axis, in radians
+    pub fn from_angles(theta:f32, phi:f32, offset:f32) -> ObliquePlane {
+        ObliquePlane {
+            normal: [theta.sin() * phi.cos(), theta.sin() * phi.sin(), theta.cos()],
+            offset,
+            ..ObliquePlane::default()
+        }
+    }
+
+    /// unit normal and the two in-plane unit vectors [n,u,v]. For a normal along z, u and v are x and y
+    pub fn basis(&self) -> Result<[[f32;3];3],ViewError> {
+        let n = normalize(self.normal).ok_or(ViewError::BadPlane)?;
+        let helper = if n[2].abs() < 0.9 { [0.,0.,1.] } else { [0.,1.,0.] };
+        let u = normalize(cross(helper,n)).ok_or(ViewError::BadPlane)?;
+        let v = cross(n,u);
+        Ok([n,u,v])
+    }
+
+    /// spacing of the samples of the plane in mm, which is the smallest voxel size so no resolution is lost
+    pub fn step(&self) -> f32 {
+        let step = self.voxel_size.into_iter().fold(f32::INFINITY,f32::min);
+        if step > 0. && step.is_finite() { step } else { 1. }
+    }
+
+    /// the output is a square big enough to hold the volume diagonal
+    pub fn slice_dims(&self, dims:&ArrayDim) -> ArrayDim {
+        let shape = dims.shape();
+        let diag = (0..3).map(|i| (shape[self.volume_dims[i]] as f32 * self.voxel_size[i]).powi(2)).sum::<f32>().sqrt();
+        let n = (diag / self.step()).ceil() as usize;
+        ArrayDim::from_shape(&[n,n])
+    }
+
+    /// samples the plane with trilinear interpolation into `out`. Points outside of the volume are set to zero.
+    /// Returns the dimensions of the slice.
+    pub fn extract(&self, cfl_buffer:&CflBuffer, out:&mut [Complex32]) -> Result<ArrayDim,ViewError> {
+        let dims = &cfl_buffer.dims;
+        let shape = dims.shape();
+        for &d in &self.volume_dims {
+            if d >= N_DIMS {
+                return Err(ViewError::BadIndex(d));
+            }
+        }
+        for (dim,&idx) in self.fixed.iter().enumerate() {
+            if !self.volume_dims.contains(&dim) && idx >= shape[dim] {
+                return Err(ViewError::IndexOutOfBounds { dim, index: idx, size: shape[dim] });
+            }
+        }
+
+        let slice_dims = self.slice_dims(dims);
+        let width = slice_dims.shape()[0];
+        let height = slice_dims.shape()[1];
+        let n_samples = slice_dims.numel();
+        if out.len() < n_samples {
+            return Err(ViewError::BufferSize { needed: n_samples, got: out.len() });
+        }
+        if n_samples == 0 {
+            return Ok(slice_dims)
+        }
+
+        let [n,u,v] = self.basis()?;
+        let vol_shape = self.volume_dims.map(|d| shape[d]);
+        let strides = dims.strides();
+        let vol_strides = self.volume_dims.map(|d| strides[d]);
+
+        let mut origin = self.fixed;
+        for &d in &self.volume_dims {
+            origin[d] = 0;
+        }
+        let base = dims.calc_addr(&origin);
+
+        // the basis is in mm, so steps along it are scaled to voxels per axis to keep anisotropic volumes unsheared
+        let step = self.step();
+        let voxel_size = self.voxel_size.map(|size| if size > 0. { size } else { 1. });
+        let u:[f32;3] = std::array::from_fn(|i| u[i] * step / voxel_size[i]);
+        let v:[f32;3] = std::array::from_fn(|i| v[i] * step / voxel_size[i]);
+        // plane center in voxel coordinates
+        let center:[f32;3] = std::array::from_fn(|i| (vol_shape[i] as f32 - 1.) / 2. + self.offset * n[i] / voxel_size[i]);
+        let half_w = (width as f32 - 1.) / 2.;
+        let half_h = (height as f32 - 1.) / 2.;
+
+        out[0..n_samples].par_chunks_mut(width).enumerate().for_each(|(y,row)|{
+            let dy = y as f32 - half_h;
+            row.iter_mut().enumerate().for_each(|(x,sample)|{
+                let dx = x as f32 - half_w;
+                let p:[f32;3] = std::array::from_fn(|i| center[i] + dx * u[i] + dy * v[i]);
+                *sample = trilinear(&cfl_buffer.data,base,vol_shape,vol_strides,p);
+            });
+        });
+
+        Ok(slice_dims)
+    }
+
+}
+
+/// interpolates the volume at p, returning zero outside of it
+fn trilinear(data:&[Complex32], base:usize, shape:[usize;3], strides:[usize;3], p:[f32;3]) -> Complex32 {
+    let mut i0 = [0usize;3];
+    let mut i1 = [0usize;3];
+    let mut w = [0f32;3];
+    for a in 0..3 {
+        let max = shape[a] as f32 - 1.;
+        if !(p[a] >= 0. && p[a] <= max) {
+            return Complex32::ZERO;
+        }
+        let f = p[a].floor();
+        i0[a] = f as usize;
+        i1[a] = (i0[a] + 1).min(shape[a] - 1);
+        w[a] = p[a] - f;
+    }
+    let mut acc = Complex32::ZERO;
+    for corner in 0..8 {
+        let mut addr = base;
+        let mut weight = 1.;
+        for a in 0..3 {
+            if corner >> a & 1 == 1 {
+                addr += i1[a] * strides[a];
+                weight *= w[a];
+            }else {
+                addr += i0[a] * strides[a];
+                weight *= 1. - w[a];
+            }
+        }
+        if weight != 0. {
+            acc += data[addr] * weight;
+        }
+    }
+    acc
+}
+
+fn cross(a:[f32;3], b:[f32;3]) -> [f32;3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn normalize(a:[f32;3]) -> Option<[f32;3]> {
+    let norm = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > f32::EPSILON {
+        Some(a.map(|x| x / norm))
+    }else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a 4x4x4 volume holding x + 10y + 100z, which trilinear interpolation reproduces exactly
+    fn linear() -> CflBuffer {
+        let dims = ArrayDim::from_shape(&[4,4,4]);
+        let data = (0..64).map(|n| Complex32::new((n % 4 + 10 * (n / 4 % 4) + 100 * (n / 16)) as f32,0.)).collect();
+        CflBuffer::new(data,dims)
+    }
+
+    fn sample(plane:&ObliquePlane, cfl_buffer:&CflBuffer, x:usize, y:usize) -> f32 {
+        let dims = plane.slice_dims(&cfl_buffer.dims);
+        let mut out = vec![Complex32::ZERO;dims.numel()];
+        plane.extract(cfl_buffer,&mut out).unwrap();
+        out[y * dims.shape()[0] + x].re
+    }
+
+    #[test]
+    fn axial_plane() {
+        let cfl_buffer = linear();
+        let plane = ObliquePlane::default();
+        // the diagonal of the volume is 6.9 voxels, and the center pixel (3,3) lands on the volume center
+        assert_eq!(plane.slice_dims(&cfl_buffer.dims).shape()[..2],[7,7]);
+        assert_eq!(sample(&plane,&cfl_buffer,3,3),1.5 + 15. + 150.);
+        assert_eq!(sample(&plane,&cfl_buffer,4,2),2.5 + 5. + 150.);
+        assert_eq!(sample(&plane,&cfl_buffer,0,3),0.);
+        let shifted = ObliquePlane { offset: 1., ..plane };
+        assert_eq!(sample(&shifted,&cfl_buffer,3,3),1.5 + 15. + 250.);
+    }
+
+    #[test]
+    fn anisotropic_plane() {
+        let cfl_buffer = linear();
+        // a sagittal plane 1 mm right of center through voxels twice as long along z
+        let plane = ObliquePlane { normal: [1.,0.,0.], offset: 1., voxel_size: [1.,1.,2.], ..ObliquePlane::default() };
+        assert_eq!(plane.step(),1.);
+        let [n,u,v] = plane.basis().unwrap();
+        assert_eq!((n,u,v),([1.,0.,0.],[0.,1.,0.],[0.,0.,1.]));
+        assert_eq!(plane.slice_dims(&cfl_buffer.dims).shape()[..2],[10,10]);
+        // pixel (5,5) is half a pixel past the center along u and v, which is half a voxel along y and a quarter along z
+        assert_eq!(sample(&plane,&cfl_buffer,5,5),2.5 + 20. + 175.);
+    }
+
+    #[test]
+    fn degenerate_normal() {
+        let cfl_buffer = linear();
+        let plane = ObliquePlane { normal: [0.,0.,0.], ..ObliquePlane::default() };
+        assert!(matches!(plane.extract(&cfl_buffer,&mut [Complex32::ZERO;49]),Err(ViewError::BadPlane)));
+    }
+}
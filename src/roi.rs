@@ -0,0 +1,221 @@
+use std::fmt::{Display, Formatter};
+use array_lib::ArrayDim;
+use array_lib::cfl::num_complex::Complex32;
+
+/// number of points used to outline an ellipse
+const ELLIPSE_SEGMENTS:usize = 64;
+
+/// a region of interest in the pixel coordinates of a slice, where pixel (x,y) covers [x,x+1) x [y,y+1)
+#[derive(Debug, Clone, PartialEq)]
+pub enum RoiShape {
+    /// rectangle between two corners
+    Rectangle { from: [f32;2], to: [f32;2] },
+    /// ellipse inscribed in the rectangle between two corners
+    Ellipse { from: [f32;2], to: [f32;2] },
+    /// closed polygon through the points
+    Freehand(Vec<[f32;2]>),
+}
+
+impl RoiShape {
+
+    /// true if the point lies inside the region
+    pub fn contains(&self, p:[f32;2]) -> bool {
+        match self {
+            RoiShape::Rectangle { from, to } => {
+                let (lo,hi) = corners(from,to);
+                p[0] >= lo[0] && p[0] <= hi[0] && p[1] >= lo[1] && p[1] <= hi[1]
+            }
+            RoiShape::Ellipse { from, to } => {
+                let (lo,hi) = corners(from,to);
+                let rx = (hi[0] - lo[0]) / 2.;
+                let ry = (hi[1] - lo[1]) / 2.;
+                if rx <= 0. || ry <= 0. {
+                    return false
+                }
+                let dx = (p[0] - (lo[0] + rx)) / rx;
+                let dy = (p[1] - (lo[1] + ry)) / ry;
+                dx * dx + dy * dy <= 1.
+            }
+            RoiShape::Freehand(points) => {
+                // even-odd rule
+                let mut inside = false;
+                let n = points.len();
+                for i in 0..n {
+                    let a = points[i];
+                    let b = points[(i + n - 1) % n];
+                    if (a[1] > p[1]) != (b[1] > p[1])
+                        && p[0] < (b[0] - a[0]) * (p[1] - a[1]) / (b[1] - a[1]) + a[0] {
+                        inside = !inside;
+                    }
+                }
+                inside
+            }
+        }
+    }
+
+    /// the outline of the region as a closed polygon for drawing
+    pub fn outline(&self) -> Vec<[f32;2]> {
+        match self {
+            RoiShape::Rectangle { from, to } => {
+                vec![*from,[to[0],from[1]],*to,[from[0],to[1]]]
+            }
+            RoiShape::Ellipse { from, to } => {
+                let c = [(from[0] + to[0]) / 2.,(from[1] + to[1]) / 2.];
+                let r = [(to[0] - from[0]) / 2.,(to[1] - from[1]) / 2.];
+                (0..ELLIPSE_SEGMENTS).map(|i|{
+                    let t = i as f32 / ELLIPSE_SEGMENTS as f32 * std::f32::consts::TAU;
+                    [c[0] + r[0] * t.cos(),c[1] + r[1] * t.sin()]
+                }).collect()
+            }
+            RoiShape::Freehand(points) => points.clone(),
+        }
+    }
+
+    /// the corners of the smallest rectangle around the region
+    pub fn bounds(&self) -> ([f32;2],[f32;2]) {
+        self.outline().iter().fold(
+            ([f32::INFINITY;2],[f32::NEG_INFINITY;2]),
+            |(lo,hi),p| ([lo[0].min(p[0]),lo[1].min(p[1])],[hi[0].max(p[0]),hi[1].max(p[1])]),
+        )
+    }
+
+    /// the samples of a slice with their pixel center inside the region
+    pub fn samples<'a>(&'a self, data:&'a [Complex32], dims:&ArrayDim) -> impl Iterator<Item=Complex32> + 'a {
+        let width = dims.shape()[0].max(1);
+        data.iter().enumerate().filter_map(move |(i,&sample)|{
+            let p = [(i % width) as f32 + 0.5,(i / width) as f32 + 0.5];
+            self.contains(p).then_some(sample)
+        })
+    }
+
+}
+
+fn corners(a:&[f32;2], b:&[f32;2]) -> ([f32;2],[f32;2]) {
+    ([a[0].min(b[0]),a[1].min(b[1])],[a[0].max(b[0]),a[1].max(b[1])])
+}
+
+/// statistics of the magnitude of the samples in a region
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RoiStats {
+    pub count: usize,
+    pub mean: f32,
+    /// sample standard deviation
+    pub std: f32,
+    pub min: f32,
+    pub max: f32,
+    /// phase of the complex sum of the samples
+    pub mean_phase: f32,
+}
+
+impl RoiStats {
+
+    /// returns None if the region doesn't cover any pixel centers
+    pub fn new(roi:&RoiShape, data:&[Complex32], dims:&ArrayDim) -> Option<RoiStats> {
+        let mut count = 0;
+        let mut sum = 0f64;
+        let mut sum_sq = 0f64;
+        let mut min = f32::INFINITY;
+        let mut max = f32::NEG_INFINITY;
+        let mut complex_sum = Complex32::ZERO;
+        for sample in roi.samples(data,dims) {
+            let mag = sample.norm();
+            count += 1;
+            sum += mag as f64;
+            sum_sq += (mag as f64) * (mag as f64);
+            min = min.min(mag);
+            max = max.max(mag);
+            complex_sum += sample;
+        }
+        if count == 0 {
+            return None
+        }
+        let mean = sum / count as f64;
+        let var = if count > 1 {
+            ((sum_sq - sum * mean) / (count - 1) as f64).max(0.)
+        }else {
+            0.
+        };
+        Some(RoiStats {
+            count,
+            mean: mean as f32,
+            std: var.sqrt() as f32,
+            min,
+            max,
+            mean_phase: complex_sum.arg(),
+        })
+    }
+
+    /// signal to noise ratio of this region against a noise region, if the noise has a spread to divide by
+    pub fn snr(&self, noise:&RoiStats) -> Option<f32> {
+        Some(self.mean / noise.std).filter(|snr| snr.is_finite())
+    }
+
+}
+
+impl Display for RoiStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "n: {}\nmean: {:.4e}\nstd: {:.4e}\nmin: {:.4e}\nmax: {:.4e}\nmean phase: {:.4}",
+            self.count,self.mean,self.std,self.min,self.max,self.mean_phase
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a 4x3 slice where pixel (x,y) holds 1 + x + 4y
+    fn ramp() -> (Vec<Complex32>,ArrayDim) {
+        ((1..=12).map(|v| Complex32::new(v as f32,0.)).collect(),ArrayDim::from_shape(&[4,3]))
+    }
+
+    #[test]
+    fn stats_of_a_rectangle() {
+        let (data,dims) = ramp();
+        let roi = RoiShape::Rectangle { from:[2.,2.], to:[0.,0.] };
+        let stats = RoiStats::new(&roi,&data,&dims).unwrap();
+        assert_eq!(stats.count,4);
+        assert_eq!(stats.mean,3.5);
+        assert!((stats.std - (17f32 / 3.).sqrt()).abs() < 1e-6);
+        assert_eq!((stats.min,stats.max),(1.,6.));
+        assert_eq!(stats.mean_phase,0.);
+    }
+
+    #[test]
+    fn snr() {
+        let (data,dims) = ramp();
+        let signal = RoiStats::new(&RoiShape::Rectangle { from:[0.,0.], to:[4.,1.] },&data,&dims).unwrap();
+        let noise = RoiStats::new(&RoiShape::Rectangle { from:[0.,1.], to:[2.,3.] },&data,&dims).unwrap();
+        // signal mean 2.5, noise samples 5,6,9,10 have a std of sqrt(17/3)
+        assert!((signal.snr(&noise).unwrap() - 2.5 / (17f32 / 3.).sqrt()).abs() < 1e-6);
+        let single = RoiStats::new(&RoiShape::Rectangle { from:[0.,0.], to:[1.,1.] },&data,&dims).unwrap();
+        assert_eq!(single.std,0.);
+        assert_eq!(signal.snr(&single),None);
+        let zeros = RoiStats::new(&RoiShape::Rectangle { from:[0.,0.], to:[1.,1.] },&[Complex32::ZERO;12],&dims).unwrap();
+        assert_eq!(zeros.snr(&zeros),None);
+    }
+
+    #[test]
+    fn shapes() {
+        let (data,dims) = ramp();
+        let ellipse = RoiShape::Ellipse { from:[0.,0.], to:[4.,3.] };
+        let values:Vec<f32> = ellipse.samples(&data,&dims).map(|x| x.re).collect();
+        assert_eq!(values,[2.,3.,5.,6.,7.,8.,10.,11.]);
+        let triangle = RoiShape::Freehand(vec![[0.,0.],[4.,0.],[0.,3.]]);
+        let values:Vec<f32> = triangle.samples(&data,&dims).map(|x| x.re).collect();
+        assert_eq!(values,[1.,2.,3.,5.,6.,9.]);
+        assert_eq!(triangle.bounds(),([0.,0.],[4.,3.]));
+        assert!(RoiStats::new(&RoiShape::Rectangle { from:[0.,0.], to:[0.2,0.2] },&data,&dims).is_none());
+    }
+
+    #[test]
+    fn mean_phase() {
+        let data = [Complex32::new(0.,1.),Complex32::new(0.,2.)];
+        let roi = RoiShape::Rectangle { from:[0.,0.], to:[2.,1.] };
+        let stats = RoiStats::new(&roi,&data,&ArrayDim::from_shape(&[2,1])).unwrap();
+        assert_eq!(stats.mean,1.5);
+        assert!((stats.mean_phase - std::f32::consts::FRAC_PI_2).abs() < 1e-6);
+    }
+}
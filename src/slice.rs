@@ -0,0 +1,513 @@
+use array_lib::ArrayDim;
+use array_lib::cfl::num_complex::Complex32;
+use rayon::prelude::*;
+use crate::cfl_buffer::{CflBuffer, DEFAULT_DIMS};
+use std::fmt::{Display, Formatter};
+use crate::ViewError;
+
+/// number of dimensions in a cfl array
+pub const N_DIMS:usize = 16;
+
+/// circular shift of an output axis that moves the center of the axis to its start or back
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AxisShift {
+    #[default]
+    None,
+    /// shift by n / 2 like numpy's `fftshift`
+    FftShift,
+    /// shift by n - n / 2, undoing `FftShift`
+    IfftShift,
+}
+
+impl AxisShift {
+    pub const ALL: [AxisShift;3] = [AxisShift::None, AxisShift::FftShift, AxisShift::IfftShift];
+
+    /// number of samples the axis is rotated by for an axis of length n
+    pub fn amount(&self, n:usize) -> usize {
+        match self {
+            AxisShift::None => 0,
+            AxisShift::FftShift => n / 2,
+            AxisShift::IfftShift => n - n / 2,
+        }
+    }
+
+    /// the shift that gives the same result on a reversed axis
+    pub fn reversed(&self) -> AxisShift {
+        match self {
+            AxisShift::None => AxisShift::None,
+            AxisShift::FftShift => AxisShift::IfftShift,
+            AxisShift::IfftShift => AxisShift::FftShift,
+        }
+    }
+}
+
+impl Display for AxisShift {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AxisShift::None => write!(f, "no shift"),
+            AxisShift::FftShift => write!(f, "fftshift"),
+            AxisShift::IfftShift => write!(f, "ifftshift"),
+        }
+    }
+}
+
+/// orientation and centering of an extracted slice. The transpose is applied first, then the flips and
+/// finally the shifts, all on the axes of the output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SliceTransform {
+    /// swap the horizontal and vertical axes of the output
+    pub transpose: bool,
+    /// mirror the output left to right
+    pub flip_x: bool,
+    /// mirror the output top to bottom
+    pub flip_y: bool,
+    pub shift_x: AxisShift,
+    pub shift_y: AxisShift,
+}
+
+impl SliceTransform {
+
+    pub fn transposed(self) -> SliceTransform {
+        SliceTransform {
+            transpose: !self.transpose,
+            flip_x: self.flip_y,
+            flip_y: self.flip_x,
+            shift_x: self.shift_y,
+            shift_y: self.shift_x,
+        }
+    }
+
+    pub fn flipped_x(self) -> SliceTransform {
+        SliceTransform { flip_x: !self.flip_x, shift_x: self.shift_x.reversed(), ..self }
+    }
+
+    pub fn flipped_y(self) -> SliceTransform {
+        SliceTransform { flip_y: !self.flip_y, shift_y: self.shift_y.reversed(), ..self }
+    }
+
+    /// the output turned 90 degrees clockwise
+    pub fn rotated_cw(self) -> SliceTransform {
+        self.transposed().flipped_x()
+    }
+
+    /// the output turned 90 degrees counter-clockwise
+    pub fn rotated_ccw(self) -> SliceTransform {
+        self.transposed().flipped_y()
+    }
+
+    /// the source coordinate along an output axis of length n
+    fn to_source(x:usize, n:usize, flip:bool, shift:AxisShift) -> usize {
+        let x = (x + n - shift.amount(n)) % n;
+        if flip { n - 1 - x } else { x }
+    }
+
+    /// the output coordinate of a source coordinate along an axis of length n. The inverse of `to_source`
+    fn to_output(x:usize, n:usize, flip:bool, shift:AxisShift) -> usize {
+        let x = if flip { n - 1 - x } else { x };
+        (x + shift.amount(n)) % n
+    }
+
+}
+
+/// describes a 2-D plane through a cfl array and how it is laid out in the output buffer
+#[derive(Debug, Clone, Copy)]
+pub struct SliceSpec {
+    /// cfl dimension along the horizontal axis of the slice
+    pub row_dim: usize,
+    /// cfl dimension along the vertical axis of the slice
+    pub col_dim: usize,
+    /// index into every other dimension. Entries for the row and column dims are ignored
+    pub fixed: [usize;N_DIMS],
+    pub transform: SliceTransform,
+}
+
+impl SliceSpec {
+
+    pub fn new(row_dim:usize, col_dim:usize) -> SliceSpec {
+        SliceSpec {
+            row_dim,
+            col_dim,
+            fixed: [0;N_DIMS],
+            transform: SliceTransform::default(),
+        }
+    }
+
+    /// cfl dimensions along the (horizontal, vertical) axes of the output
+    pub fn output_axes(&self) -> (usize,usize) {
+        if self.transform.transpose {
+            (self.col_dim,self.row_dim)
+        }else {
+            (self.row_dim,self.col_dim)
+        }
+    }
+
+    /// dimensions of the extracted slice as [width, height]
+    pub fn slice_dims(&self, dims:&ArrayDim) -> ArrayDim {
+        let (h,v) = self.output_axes();
+        ArrayDim::from_shape(&[dims.shape()[h],dims.shape()[v]])
+    }
+
+    /// checks the spec against the array dimensions
+    pub fn validate(&self, dims:&ArrayDim) -> Result<(),ViewError> {
+        if self.row_dim >= N_DIMS {
+            return Err(ViewError::BadIndex(self.row_dim));
+        }
+        if self.col_dim >= N_DIMS || self.col_dim == self.row_dim {
+            return Err(ViewError::BadIndex(self.col_dim));
+        }
+        let shape = dims.shape();
+        for (dim,&idx) in self.fixed.iter().enumerate() {
+            if dim != self.row_dim && dim != self.col_dim && idx >= shape[dim] {
+                return Err(ViewError::IndexOutOfBounds { dim, index: idx, size: shape[dim] });
+            }
+        }
+        Ok(())
+    }
+
+    /// maps a (x,y) pixel of the output back to the full cfl index
+    pub fn cfl_index(&self, dims:&ArrayDim, x:usize, y:usize) -> [usize;N_DIMS] {
+        let (h,v) = self.output_axes();
+        let shape = dims.shape();
+        let t = &self.transform;
+        let mut idx = self.fixed;
+        idx[h] = SliceTransform::to_source(x,shape[h],t.flip_x,t.shift_x);
+        idx[v] = SliceTransform::to_source(y,shape[v],t.flip_y,t.shift_y);
+        idx
+    }
+
+    /// maps a full cfl index to the (x,y) pixel of the output it lands on. The inverse of `cfl_index`
+    pub fn pixel(&self, dims:&ArrayDim, idx:&[usize;N_DIMS]) -> (usize,usize) {
+        let (h,v) = self.output_axes();
+        let shape = dims.shape();
+        let t = &self.transform;
+        let x = SliceTransform::to_output(idx[h],shape[h],t.flip_x,t.shift_x);
+        let y = SliceTransform::to_output(idx[v],shape[v],t.flip_y,t.shift_y);
+        (x,y)
+    }
+
+    /// copies the plane out of the cfl buffer into `out`, which must hold at least as many samples as the
+    /// slice. The horizontal axis is the fastest varying. Returns the dimensions of the slice.
+    pub fn extract(&self, cfl_buffer:&CflBuffer, out:&mut [Complex32]) -> Result<ArrayDim,ViewError> {
+        self.validate(&cfl_buffer.dims)?;
+        let slice_dims = self.slice_dims(&cfl_buffer.dims);
+        let n = slice_dims.numel();
+        if out.len() < n {
+            return Err(ViewError::BufferSize { needed: n, got: out.len() });
+        }
+        if n == 0 {
+            return Ok(slice_dims)
+        }
+
+        let (h,v) = self.output_axes();
+        let strides = cfl_buffer.dims.strides();
+        let width = slice_dims.shape()[0];
+        let height = slice_dims.shape()[1];
+
+        let mut origin = self.fixed;
+        origin[self.row_dim] = 0;
+        origin[self.col_dim] = 0;
+        let base = cfl_buffer.dims.calc_addr(&origin);
+
+        let t = self.transform;
+        out[0..n].par_chunks_mut(width).enumerate().for_each(|(y,row)|{
+            let y = SliceTransform::to_source(y,height,t.flip_y,t.shift_y);
+            let row_addr = base + y * strides[v];
+            row.iter_mut().enumerate().for_each(|(x,sample)|{
+                let x = SliceTransform::to_source(x,width,t.flip_x,t.shift_x);
+                *sample = cfl_buffer.data[row_addr + x * strides[h]];
+            });
+        });
+
+        Ok(slice_dims)
+    }
+
+}
+
+/// determines which cfl slices are displayed for three orthogonal views
+pub struct SliceHandler {
+    /// the cfl dimensions corresponding to the view (x,y,z) dims
+    pub view_slices: [usize;3],
+    /// the slices to render from the cfl
+    pub slice_indices: [usize;3],
+    /// index into the dimensions not covered by the views
+    pub fixed: [usize;N_DIMS],
+    /// orientation and centering of each view
+    pub transforms: [SliceTransform;3],
+    slice_views: [Vec<Complex32>;3],
+    slice_dims: [ArrayDim;3],
+}
+
+impl From<ArrayDim> for SliceHandler {
+    fn from(dims: ArrayDim) -> SliceHandler {
+        let mut sh = SliceHandler::default();
+        let shape = dims.shape();
+        // center the view for 'x' 'y' 'z'
+        for (idx,&dim) in sh.slice_indices.iter_mut().zip(sh.view_slices.iter()) {
+            *idx = shape[dim] / 2;
+        }
+        for view in 0..3 {
+            sh.slice_dims[view] = sh.spec(view).slice_dims(&dims);
+            sh.slice_views[view] = vec![Complex32::ZERO;sh.slice_dims[view].numel()];
+        }
+        sh
+    }
+}
+
+impl SliceHandler {
+
+    /// slice spec for view 0 (x-y plane), 1 (y-z plane) or 2 (z-x plane)
+    pub fn spec(&self, view:usize) -> SliceSpec {
+        let row = view % 3;
+        let col = (view + 1) % 3;
+        let mut spec = SliceSpec::new(self.view_slices[row],self.view_slices[col]);
+        spec.fixed = self.position();
+        spec.transform = self.transforms[view];
+        spec
+    }
+
+    /// the full cfl index at the intersection of the three views
+    pub fn position(&self) -> [usize;N_DIMS] {
+        let mut idx = self.fixed;
+        for (&dim,&i) in self.view_slices.iter().zip(self.slice_indices.iter()) {
+            idx[dim] = i;
+        }
+        idx
+    }
+
+    /// moves the intersection of the three views to a full cfl index
+    pub fn set_position(&mut self, idx:&[usize;N_DIMS]) {
+        for (&dim,i) in self.view_slices.iter().zip(self.slice_indices.iter_mut()) {
+            *i = idx[dim];
+        }
+        self.fixed = *idx;
+    }
+
+    /// updates the internal slice buffer of a view based on the current slice indices
+    pub fn update_slice(&mut self, view:usize, cfl_buffer: &CflBuffer) -> Result<(),ViewError> {
+        let spec = self.spec(view);
+        let n = spec.slice_dims(&cfl_buffer.dims).numel();
+        if self.slice_views[view].len() < n {
+            self.slice_views[view].resize(n,Complex32::ZERO);
+        }
+        self.slice_dims[view] = spec.extract(cfl_buffer,&mut self.slice_views[view])?;
+        Ok(())
+    }
+
+    /// updates all three views
+    pub fn update_all(&mut self, cfl_buffer: &CflBuffer) -> Result<(),ViewError> {
+        for view in 0..3 {
+            self.update_slice(view,cfl_buffer)?;
+        }
+        Ok(())
+    }
+
+    /// the most recently extracted slice data of a view
+    pub fn slice_view(&self, view:usize) -> (&[Complex32],ArrayDim) {
+        let dims = self.slice_dims[view];
+        (&self.slice_views[view][0..dims.numel()],dims)
+    }
+}
+
+impl Default for SliceHandler {
+    fn default() -> SliceHandler {
+        SliceHandler {
+            view_slices: [0,1,2],
+            slice_indices: [0,0,0],
+            fixed: [0;N_DIMS],
+            transforms: [SliceTransform::default();3],
+            slice_views: [
+                vec![Complex32::ZERO;DEFAULT_DIMS*DEFAULT_DIMS],
+                vec![Complex32::ZERO;DEFAULT_DIMS*DEFAULT_DIMS],
+                vec![Complex32::ZERO;DEFAULT_DIMS*DEFAULT_DIMS],
+            ],
+            slice_dims: [ArrayDim::from_shape(&[DEFAULT_DIMS, DEFAULT_DIMS]);3],
+        }
+    }
+}
+
+/// slices stepping through one dimension of a cfl, as shown in a lightbox
+#[derive(Debug, Clone, Copy)]
+pub struct SliceSeries {
+    /// the plane of every slice in the series
+    pub spec: SliceSpec,
+    /// the dimension to step through
+    pub dim: usize,
+    pub start: usize,
+    pub step: usize,
+}
+
+impl SliceSeries {
+
+    pub fn new(spec:SliceSpec, dim:usize) -> SliceSeries {
+        SliceSeries {
+            spec,
+            dim,
+            start: 0,
+            step: 1,
+        }
+    }
+
+    /// number of slices in the series before running off the end of the dimension
+    pub fn len(&self, dims:&ArrayDim) -> usize {
+        if self.dim >= N_DIMS || self.dim == self.spec.row_dim || self.dim == self.spec.col_dim {
+            return 0
+        }
+        let n = dims.shape()[self.dim];
+        if self.start >= n {
+            0
+        }else {
+            (n - self.start).div_ceil(self.step.max(1))
+        }
+    }
+
+    pub fn is_empty(&self, dims:&ArrayDim) -> bool {
+        self.len(dims) == 0
+    }
+
+    /// the spec of the i-th slice of the series
+    pub fn spec_at(&self, i:usize) -> SliceSpec {
+        let mut spec = self.spec;
+        spec.fixed[self.dim] = self.start + i * self.step.max(1);
+        spec
+    }
+
+    /// the slice specs of the series in order
+    pub fn specs(&self, dims:&ArrayDim) -> Vec<SliceSpec> {
+        (0..self.len(dims)).map(|i| self.spec_at(i)).collect()
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a 3x2x4 array where sample [i,j,k] holds i + 10j + 100k
+    fn ramp() -> CflBuffer {
+        let dims = ArrayDim::from_shape(&[3,2,4]);
+        let data = (0..24).map(|n| Complex32::new((n % 3 + 10 * (n / 3 % 2) + 100 * (n / 6)) as f32,0.)).collect();
+        CflBuffer::new(data,dims)
+    }
+
+    #[test]
+    fn extracts_plane() {
+        let cfl_buffer = ramp();
+        let mut spec = SliceSpec::new(0,2);
+        spec.fixed[1] = 1;
+        let mut out = vec![Complex32::ZERO;12];
+        let dims = spec.extract(&cfl_buffer,&mut out).unwrap();
+        assert_eq!(dims.shape()[..2],[3,4]);
+        let expected:Vec<f32> = (0..12).map(|n| (n % 3 + 10 + 100 * (n / 3)) as f32).collect();
+        assert_eq!(out.iter().map(|x| x.re).collect::<Vec<_>>(),expected);
+        assert!(matches!(spec.extract(&cfl_buffer,&mut out[..11]),Err(ViewError::BufferSize { needed: 12, got: 11 })));
+    }
+
+    #[test]
+    fn index_mapping() {
+        let cfl_buffer = ramp();
+        let mut spec = SliceSpec::new(2,0);
+        spec.fixed[1] = 1;
+        let mut out = vec![Complex32::ZERO;12];
+        spec.extract(&cfl_buffer,&mut out).unwrap();
+        for (x,y) in [(0,0),(3,1),(2,2)] {
+            let idx = spec.cfl_index(&cfl_buffer.dims,x,y);
+            assert_eq!(idx[..3],[y,1,x]);
+            assert_eq!(cfl_buffer.get(&idx),Some(out[y * 4 + x]));
+            assert_eq!(spec.pixel(&cfl_buffer.dims,&idx),(x,y));
+        }
+    }
+
+    /// the [i,j] plane of the ramp at k = 0, [0,1,2; 10,11,12] untransformed, as (width, height, values)
+    fn plane(transform:SliceTransform) -> (usize,usize,Vec<f32>) {
+        let mut spec = SliceSpec::new(0,1);
+        spec.transform = transform;
+        let mut out = vec![Complex32::ZERO;6];
+        let dims = spec.extract(&ramp(),&mut out).unwrap();
+        (dims.shape()[0],dims.shape()[1],out.iter().map(|x| x.re).collect())
+    }
+
+    #[test]
+    fn transforms() {
+        let t = SliceTransform::default();
+        assert_eq!(plane(t),(3,2,vec![0.,1.,2.,10.,11.,12.]));
+        assert_eq!(plane(t.transposed()),(2,3,vec![0.,10.,1.,11.,2.,12.]));
+        assert_eq!(plane(t.flipped_x()),(3,2,vec![2.,1.,0.,12.,11.,10.]));
+        assert_eq!(plane(t.flipped_y()),(3,2,vec![10.,11.,12.,0.,1.,2.]));
+        assert_eq!(plane(t.rotated_cw()),(2,3,vec![10.,0.,11.,1.,12.,2.]));
+        assert_eq!(plane(t.rotated_ccw()),(2,3,vec![2.,12.,1.,11.,0.,10.]));
+        assert_eq!(t.rotated_cw().rotated_ccw(),t);
+        assert_eq!(t.rotated_cw().rotated_cw(),t.flipped_x().flipped_y());
+    }
+
+    #[test]
+    fn shifts() {
+        let fftshift = SliceTransform { shift_x: AxisShift::FftShift, ..SliceTransform::default() };
+        let ifftshift = SliceTransform { shift_x: AxisShift::IfftShift, ..SliceTransform::default() };
+        assert_eq!(plane(fftshift).2,[2.,0.,1.,12.,10.,11.]);
+        assert_eq!(plane(ifftshift).2,[1.,2.,0.,11.,12.,10.]);
+        // flipping a shifted slice mirrors the shifted output
+        assert_eq!(plane(fftshift.flipped_x()).2,[1.,0.,2.,11.,10.,12.]);
+        // the shift follows its source axis through a transpose, onto the rows
+        assert_eq!(plane(fftshift.transposed()).2,[2.,12.,0.,10.,1.,11.]);
+        assert_eq!(plane(fftshift.transposed().transposed()),plane(fftshift));
+    }
+
+    #[test]
+    fn index_mapping_under_transforms() {
+        let cfl_buffer = ramp();
+        let mut out = vec![Complex32::ZERO;12];
+        for bits in 0..8 {
+            for shift_x in AxisShift::ALL {
+                for shift_y in AxisShift::ALL {
+                    let mut spec = SliceSpec::new(2,0);
+                    spec.transform = SliceTransform { transpose: bits & 1 != 0, flip_x: bits & 2 != 0, flip_y: bits & 4 != 0, shift_x, shift_y };
+                    let dims = spec.extract(&cfl_buffer,&mut out).unwrap();
+                    let width = dims.shape()[0];
+                    for (i,sample) in out.iter().enumerate() {
+                        let (x,y) = (i % width,i / width);
+                        let idx = spec.cfl_index(&cfl_buffer.dims,x,y);
+                        assert_eq!(cfl_buffer.get(&idx),Some(*sample));
+                        assert_eq!(spec.pixel(&cfl_buffer.dims,&idx),(x,y));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn validates_spec() {
+        let dims = ramp().dims;
+        assert!(matches!(SliceSpec::new(0,0).validate(&dims),Err(ViewError::BadIndex(0))));
+        assert!(matches!(SliceSpec::new(N_DIMS,0).validate(&dims),Err(ViewError::BadIndex(N_DIMS))));
+        let mut spec = SliceSpec::new(0,1);
+        spec.fixed[2] = 4;
+        assert!(matches!(spec.validate(&dims),Err(ViewError::IndexOutOfBounds { dim: 2, index: 4, size: 4 })));
+    }
+
+    #[test]
+    fn empty_plane() {
+        let cfl_buffer = CflBuffer::new(vec![],ArrayDim::from_shape(&[0,3]));
+        let dims = SliceSpec::new(0,1).extract(&cfl_buffer,&mut []).unwrap();
+        assert_eq!(dims.numel(),0);
+    }
+
+    #[test]
+    fn handler_and_series() {
+        let cfl_buffer = ramp();
+        let mut handler = SliceHandler::from(cfl_buffer.dims);
+        assert_eq!(handler.position()[..3],[1,1,2]);
+        handler.update_all(&cfl_buffer).unwrap();
+        let (data,dims) = handler.slice_view(1);
+        assert_eq!(dims.shape()[..2],[2,4]);
+        assert_eq!(data[0].re,1.);
+        assert_eq!(data[7].re,1. + 10. + 300.);
+
+        let mut series = SliceSeries::new(SliceSpec::new(0,1),2);
+        series.start = 1;
+        series.step = 2;
+        assert_eq!(series.len(&cfl_buffer.dims),2);
+        assert_eq!(series.specs(&cfl_buffer.dims).iter().map(|spec| spec.fixed[2]).collect::<Vec<_>>(),[1,3]);
+        series.dim = 0;
+        assert!(series.is_empty(&cfl_buffer.dims));
+    }
+}
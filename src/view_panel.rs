@@ -1,11 +1,54 @@
 use array_lib::ArrayDim;
 use array_lib::cfl::num_complex::Complex32;
-use iced::{Element, Length, Point, Rectangle, Settings, Task, Theme};
+use iced::{mouse, Color, Element, Length, Point, Rectangle, Settings, Size, Subscription, Task, Theme, Vector};
 use iced::mouse::Cursor;
-use iced::widget::{button, canvas, column, container, row, text, Canvas, Image};
-use iced::widget::canvas::{Frame, Geometry, Program};
+use iced::widget::{button, canvas, checkbox, column, container, pick_list, row, scrollable, slider, text, text_input, toggler, Canvas};
+use iced::widget::canvas::{Action, Event, Frame, Geometry, Path, Program, Stroke};
 use iced::Renderer;
-use iced::widget::image::Handle;
+use iced::futures::channel::oneshot;
+use std::path::{Path as FilePath, PathBuf};
+use std::sync::Arc;
+use rfd::FileDialog;
+use iced::widget::image::{FilterMethod, Handle};
+use crate::cfl_buffer::{CflBuffer, DEFAULT_DIMS};
+use crate::slice::{AxisShift, SliceHandler, SliceSeries, SliceSpec, SliceTransform, N_DIMS};
+use crate::reslice::ObliquePlane;
+use crate::projection::{Projection, ProjectionMode};
+use crate::cine::{Cine, CineMode};
+use crate::roi::{RoiShape, RoiStats};
+use crate::profile::{dim_profile, line_profile};
+use crate::histogram::{Histogram, Window, DEFAULT_BINS};
+use crate::fft::CenteredFft;
+use crate::difference::DifferenceMode;
+use crate::export::{draw_line, draw_polygon, numbered_path, write_png, write_tiff_f32, write_tiff_u16, AnimationFormat, ExportFormat};
+use crate::nifti::{write_nifti, NiftiComponents};
+use crate::npy::write_npy;
+use crate::mat::{list_mat, read_mat, MatVariable};
+use crate::raw::{parse_shape, Endianness, RawFormat, RawLayout, RawType};
+use crate::dicom::{read_dicom_groups, DicomGroup, DicomInfo};
+use crate::ViewError;
+use iced::window::Screenshot;
+use iced_aksel::{axis, Axis, Chart, Measure, Plot, PlotData, PlotPoint};
+use iced_aksel::axis::{TickContext, TickResult};
+use iced_aksel::plot::DragDelta;
+use rayon::prelude::*;
+use iced_aksel::scale::Linear;
+use iced_aksel::shape::Line;
+
+/// upper limit on the number of lightbox panes to keep rendering responsive
+const MAX_LIGHTBOX_PANES:usize = 64;
+
+/// width of the controls column left of the pane grid
+const CONTROLS_WIDTH:f32 = 220.;
+
+// profile chart axis IDs
+const PROFILE_X_ID: &str = "position";
+const PROFILE_Y_ID: &str = "value";
+const PROFILE_PHASE_ID: &str = "phase";
+
+// histogram chart axis IDs
+const HISTOGRAM_X_ID: &str = "value";
+const HISTOGRAM_Y_ID: &str = "count";
 
 pub struct ViewPanel {
 
@@ -20,39 +63,1190 @@ pub struct ViewPanel {
 
     view_mode:ViewMode,
 
-    scaling:f32,
+    /// range of values mapped from black to white
+    window:Window,
+
+    /// full cfl array being viewed
+    cfl_buffer:CflBuffer,
+    /// label of the cfl shown when comparing files
+    name:String,
+    /// other cfls shown below the primary one at the same slice position
+    compare_files:Vec<CompareFile>,
+
+    /// the primary cfl (A) combined with a comparison file (B) in one orthogonal view
+    difference_mode:DifferenceMode,
+    difference_file:usize,
+    difference_view:usize,
+    difference_data:Vec<Complex32>,
+    difference_dims:ArrayDim,
+    /// distance from the center value shown at full color
+    difference_limit:f32,
+    /// limit covering most of the current difference image
+    difference_auto_limit:f32,
+
+    /// extracts the orthogonal slices shown in the panes
+    slice_handler:SliceHandler,
+
+    /// the orthogonal view edited by the transform controls
+    transform_view:usize,
+
+    export_format:ExportFormat,
+    /// the pane written by pane and batch exports
+    export_pane:usize,
+    /// the dimension stepped through by batch exports
+    export_dim:usize,
+    /// file type of cine exports, which step through the cine dim
+    animation_format:AnimationFormat,
+    /// cfl exports are cropped to the bounding box of the signal region
+    cfl_roi_crop:bool,
+    /// dimensions kept whole in cfl exports, besides the plane of the pane
+    cfl_full_dims:[bool;N_DIMS],
+    /// how complex samples are stored in nifti exports
+    nifti_components:NiftiComponents,
+
+    /// the last .mat file opened for comparison and its numeric variables, which are loaded one at a time
+    mat_file:Option<(PathBuf,Vec<MatVariable>)>,
+
+    /// how raw files are read. The shape and offset are parsed from the text fields when a file is opened
+    raw_format:RawFormat,
+    raw_shape:String,
+    raw_offset:String,
+
+    /// tags of the dicom series that were opened, by the name they're shown under
+    dicom_info:Vec<(String,DicomInfo)>,
+    /// the last dicom folder opened for comparison that holds several series, which are loaded one at a time
+    dicom_groups:Option<(String,Vec<DicomGroup>)>,
+    /// the last file, export or update that failed, shown with the controls until dismissed
+    last_error:Option<String>,
+
+    /// what each pane of the grid shows
+    panes:Vec<PaneContent>,
+
+    /// polar and azimuthal angle of the oblique plane normal in degrees
+    oblique_angles:[f32;2],
+    oblique_plane:ObliquePlane,
+    oblique_data:Vec<Complex32>,
+    oblique_dims:ArrayDim,
+
+    /// the projection settings shown in the controls
+    projection:Projection,
+    /// the collapsed cfl, if a projection pane is shown
+    projection_buffer:Option<CflBuffer>,
+    projection_data:Vec<Complex32>,
+    projection_dims:ArrayDim,
+
+    /// the slices shown in lightbox mode
+    lightbox:SliceSeries,
+    lightbox_data:Vec<(Vec<Complex32>,ArrayDim)>,
+    /// slices the lightbox settings select, of which at most MAX_LIGHTBOX_PANES are shown
+    lightbox_total:usize,
+
+    /// playback through one dimension
+    cine:Cine,
+
+    probe:Option<Probe>,
+
+    /// histogram of the first pane or of the whole cfl
+    histogram_plot:HistogramPlot,
+    histogram_volume:bool,
+    histogram_chart:iced_aksel::State<&'static str,f64>,
+    /// normalized x position of the cursor over the histogram
+    histogram_cursor:f32,
+    /// the window bound moved by dragging on the histogram, the upper one if true
+    histogram_drag_hi:bool,
+
+    /// centered fft applied to the loaded cfl
+    fft:CenteredFft,
+    fft_enabled:bool,
+    /// samples of the loaded cfl before the transform, kept while the transform is shown
+    raw_data:Option<Vec<Complex32>>,
+    /// transforms finishing with an older generation are stale and dropped
+    fft_generation:usize,
+    fft_busy:bool,
+
+    /// zoom and pan of each pane
+    pane_zoom:Vec<PaneZoom>,
+    /// apply zoom and pan changes to every pane
+    zoom_linked:bool,
+    zoom_mode:ZoomMode,
+    /// draw images with bilinear instead of nearest-neighbor filtering
+    smooth:bool,
+
+    roi_tool:RoiTool,
+    /// the next region drawn is the noise region
+    drawing_noise:bool,
+    /// signal region and the pane it was drawn on
+    signal_roi:Option<(usize,RoiShape)>,
+    /// noise region and the pane it was drawn on
+    noise_roi:Option<(usize,RoiShape)>,
+
+    /// line to plot a profile along, and the pane it was drawn on
+    profile_line:Option<(usize,[f32;2],[f32;2])>,
+    /// plot the profile along a cfl dimension through the crosshair instead of along the line
+    profile_along_dim:bool,
+    profile_dim:usize,
+    profile_chart:iced_aksel::State<&'static str,f64>,
+    /// magnitude, real, imaginary and phase of the profile
+    profile_series:[ProfileSeries;4],
 }
 
 #[derive(Debug, Clone)]
 pub enum ViewPanelMessage {
-    Increment
+    Increment,
+    ObliqueToggled(bool),
+    ObliqueTheta(f32),
+    ObliquePhi(f32),
+    ObliqueOffset(f32),
+    ProjectionToggled(bool),
+    ProjectionModeSelected(ProjectionMode),
+    ProjectionDimSelected(usize),
+    LightboxToggled(bool),
+    LightboxDimSelected(usize),
+    LightboxStart(u32),
+    LightboxStep(u32),
+    CinePlayToggled,
+    CineDimSelected(usize),
+    CineModeSelected(CineMode),
+    CineFps(f32),
+    CineTick,
+    /// a pane was clicked at an image pixel
+    PaneClicked(usize,(usize,usize)),
+    /// the cursor moved onto an image pixel of a pane, or left it
+    PaneHovered(usize,Option<(usize,usize)>),
+    /// a pane was zoomed or panned
+    PaneZoomed(usize,PaneZoom),
+    ZoomModeSelected(ZoomMode),
+    ZoomLinkToggled(bool),
+    SmoothToggled(bool),
+    RoiToolSelected(RoiTool),
+    NoiseRoiToggled(bool),
+    /// a region was drawn on a pane
+    RoiDrawn(usize,RoiShape),
+    ClearRois,
+    /// a profile line was drawn on a pane
+    LineDrawn(usize,[f32;2],[f32;2]),
+    ProfileAlongDimToggled(bool),
+    ProfileDimSelected(usize),
+    ProfileComponentToggled(bool,ProfileComponent),
+    ClearProfile,
+    HistogramVolumeToggled(bool),
+    HistogramLogToggled(bool),
+    HistogramHovered(Point),
+    /// a click on the histogram moves the closest window bound to the cursor
+    HistogramClicked(Point),
+    /// a drag on the histogram shifts the last moved window bound by the drag distance
+    HistogramDragged(DragDelta),
+    AutoWindow,
+    FftToggled(bool),
+    FftInverseToggled(bool),
+    FftAxisToggled(usize,bool),
+    /// a background transform finished with the new samples, or None if it failed
+    FftFinished(usize,Result<Arc<Vec<Complex32>>,String>),
+    TransformViewSelected(usize),
+    /// new orientation and centering of an orthogonal view
+    SliceTransformed(usize,SliceTransform),
+    AddCompareClicked,
+    MatVariableSelected(MatVariable),
+    RawShapeEdited(String),
+    RawOffsetEdited(String),
+    RawTypeSelected(RawType),
+    RawEndiannessSelected(Endianness),
+    RawLayoutSelected(RawLayout),
+    /// open raw files, with the first replacing the primary cfl if true, or all for comparison
+    OpenRawClicked(bool),
+    RawFilesPicked(bool,Option<Vec<PathBuf>>),
+    OpenDicomClicked,
+    DicomFolderPicked(Option<PathBuf>),
+    /// the label of a group of the last dicom folder
+    DicomGroupSelected(String),
+    CompareFilesPicked(Option<Vec<PathBuf>>),
+    ClearCompare,
+    ErrorDismissed,
+    DifferenceToggled(bool),
+    DifferenceModeSelected(DifferenceMode),
+    DifferenceFileSelected(usize),
+    DifferenceViewSelected(usize),
+    DifferenceLimit(f32),
+    DifferenceAutoLimit,
+    ExportFormatSelected(ExportFormat),
+    ExportPaneSelected(usize),
+    ExportDimSelected(usize),
+    AnimationFormatSelected(AnimationFormat),
+    CflRoiCropToggled(bool),
+    CflFullDimToggled(usize,bool),
+    NiftiComponentsSelected(NiftiComponents),
+    ExportClicked(ExportTarget),
+    ExportPathPicked(ExportTarget,Option<PathBuf>),
+    GridCaptured(PathBuf,Screenshot),
+}
+
+/// the source of the image displayed in a pane
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PaneContent {
+    /// one of the three orthogonal views of the slice handler
+    Ortho(usize),
+    /// the resliced oblique plane
+    Oblique,
+    /// a slice through the projected cfl
+    Projection,
+    /// one slice of the lightbox series
+    Lightbox(usize),
+    /// an orthogonal view of a comparison file as (file,view)
+    Compare(usize,usize),
+    /// the difference between the primary cfl and a comparison file
+    Difference,
+}
+
+/// another cfl shown next to the primary one. It follows the slice position of the primary cfl as far as
+/// its dimensions allow
+struct CompareFile {
+    name:String,
+    cfl_buffer:CflBuffer,
+    /// the extracted slice of each orthogonal view
+    slices:[(Vec<Complex32>,ArrayDim);3],
 }
 
 impl Default for ViewPanel {
 
     fn default() -> Self {
-        ViewPanel {
+        ViewPanel::from(CflBuffer::default())
+    }
+
+}
+
+impl From<CflBuffer> for ViewPanel {
+
+    fn from(cfl_buffer: CflBuffer) -> Self {
+        let mut slice_handler = SliceHandler::from(cfl_buffer.dims);
+        slice_handler.update_all(&cfl_buffer).expect("default slices must be valid");
+        let mut view_panel = ViewPanel {
             n_panes: 3,
             pane_dims: [512,512],
             grid_dims: [1,3],
             view_mode: ViewMode::default(),
-            scaling: 128.0,
+            window: Window::default(),
+            cfl_buffer,
+            name: String::new(),
+            compare_files: vec![],
+            difference_mode: DifferenceMode::Difference,
+            difference_file: 0,
+            difference_view: 0,
+            difference_data: vec![],
+            difference_dims: ArrayDim::from_shape(&[0,0]),
+            difference_limit: 1.,
+            difference_auto_limit: 1.,
+            panes: vec![PaneContent::Ortho(0),PaneContent::Ortho(1),PaneContent::Ortho(2)],
+            oblique_angles: [0.,0.],
+            oblique_plane: ObliquePlane::default(),
+            oblique_data: vec![],
+            oblique_dims: ArrayDim::from_shape(&[0,0]),
+            projection: Projection::new(ProjectionMode::Max,2),
+            projection_buffer: None,
+            projection_data: vec![],
+            projection_dims: ArrayDim::from_shape(&[0,0]),
+            lightbox: SliceSeries::new(slice_handler.spec(0),2),
+            lightbox_data: vec![],
+            lightbox_total: 0,
+            cine: Cine::default(),
+            probe: None,
+            histogram_plot: HistogramPlot::default(),
+            histogram_volume: false,
+            histogram_chart: histogram_chart_state(),
+            histogram_cursor: 0.,
+            histogram_drag_hi: false,
+            fft: CenteredFft::default(),
+            fft_enabled: false,
+            raw_data: None,
+            fft_generation: 0,
+            fft_busy: false,
+            transform_view: 0,
+            export_format: ExportFormat::Png,
+            export_pane: 0,
+            export_dim: 2,
+            animation_format: AnimationFormat::Gif,
+            cfl_roi_crop: false,
+            cfl_full_dims: [false;N_DIMS],
+            nifti_components: NiftiComponents::Magnitude,
+            mat_file: None,
+            dicom_info: vec![],
+            dicom_groups: None,
+            last_error: None,
+            raw_format: RawFormat::default(),
+            raw_shape: format!("{},{}",DEFAULT_DIMS,DEFAULT_DIMS),
+            raw_offset: "0".to_string(),
+            pane_zoom: vec![PaneZoom::default();3],
+            zoom_linked: false,
+            zoom_mode: ZoomMode::Fit,
+            smooth: false,
+            roi_tool: RoiTool::Off,
+            drawing_noise: false,
+            signal_roi: None,
+            noise_roi: None,
+            profile_line: None,
+            profile_along_dim: false,
+            profile_dim: 4,
+            profile_chart: profile_chart_state(),
+            profile_series: ProfileComponent::ALL.map(ProfileSeries::new),
+            slice_handler,
+        };
+        view_panel.update_histogram();
+        view_panel.set_window(view_panel.histogram_plot.histogram.auto_window());
+        view_panel
+    }
+
+}
+
+
+/// draws the image of a pane with its overlays and turns mouse input into messages
+struct PaneCanvas {
+    pane_id:usize,
+    handle:Handle,
+    /// size of the image in pixels
+    image_size:[usize;2],
+    /// pixel of the crosshair center
+    crosshair:Option<(usize,usize)>,
+    zoom:PaneZoom,
+    zoom_mode:ZoomMode,
+    filter_method:FilterMethod,
+    /// physical size of an image pixel along x and y in mm, if known
+    pixel_size:Option<[f32;2]>,
+    /// shape drawn by dragging with the left button
+    roi_tool:RoiTool,
+    /// regions to outline on the pane
+    rois:Vec<(RoiShape,Color)>,
+    /// profile line drawn on the pane
+    profile_line:Option<([f32;2],[f32;2])>,
+    /// name of the file shown, drawn in the top left corner
+    label:String,
+}
+
+/// interaction state of a pane canvas
+#[derive(Default)]
+struct PaneState {
+    /// the pixel last reported as hovered
+    hovered:Option<(usize,usize)>,
+    /// last cursor position while panning
+    pan_from:Option<Point>,
+    /// image points of a region being drawn
+    drawing:Option<Vec<[f32;2]>>,
+}
+
+/// the kind of region drawn on a pane
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoiTool {
+    /// left clicks move the crosshair instead
+    Off,
+    Rectangle,
+    Ellipse,
+    Freehand,
+    /// a line to plot a profile along
+    Line,
+}
+
+impl RoiTool {
+    const ALL: [RoiTool;5] = [RoiTool::Off, RoiTool::Rectangle, RoiTool::Ellipse, RoiTool::Freehand, RoiTool::Line];
+
+    /// the region described by the points of a drag
+    fn shape(&self, points:&[[f32;2]]) -> Option<RoiShape> {
+        let from = *points.first()?;
+        let to = *points.last()?;
+        match self {
+            RoiTool::Off | RoiTool::Line => None,
+            RoiTool::Rectangle => Some(RoiShape::Rectangle { from, to }),
+            RoiTool::Ellipse => Some(RoiShape::Ellipse { from, to }),
+            RoiTool::Freehand => (points.len() > 2).then(|| RoiShape::Freehand(points.to_vec())),
         }
     }
+}
+
+impl std::fmt::Display for RoiTool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RoiTool::Off => write!(f, "no roi"),
+            RoiTool::Rectangle => write!(f, "rectangle"),
+            RoiTool::Ellipse => write!(f, "ellipse"),
+            RoiTool::Freehand => write!(f, "freehand"),
+            RoiTool::Line => write!(f, "line profile"),
+        }
+    }
+}
+
+/// magnification and offset of the image in a pane
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PaneZoom {
+    /// magnification relative to the zoom mode's scale
+    zoom:f32,
+    /// offset of the image from the pane center in screen pixels
+    pan:Vector,
+}
 
+impl Default for PaneZoom {
+    fn default() -> PaneZoom {
+        PaneZoom {
+            zoom: 1.,
+            pan: Vector::new(0.,0.),
+        }
+    }
 }
 
+/// the scale images are drawn at before zooming
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZoomMode {
+    /// scale the image to fill the pane
+    Fit,
+    /// one image pixel per screen pixel
+    Native,
+}
 
-struct BlankCanvas;
+/// what an export writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportTarget {
+    /// the selected pane as shown
+    Pane,
+    /// every slice of the selected pane along a dimension
+    Batch,
+    /// the whole pane grid as shown on screen
+    Grid,
+    /// playback of the selected pane along the cine dim
+    Cine,
+    /// the samples behind the selected pane as a .cfl/.hdr pair
+    Cfl,
+    /// the whole cfl as nifti
+    Nifti,
+    /// the whole cfl as a complex64 .npy
+    Npy,
+}
+
+/// the data under the mouse cursor
+struct Probe {
+    /// the pane the value was probed on
+    pane_id:usize,
+    /// full cfl index, if the pane is an axis-aligned slice
+    index:Option<[usize;N_DIMS]>,
+    value:Complex32,
+    /// gray level the value is rendered with, or the signed value mapped to the colormap of a difference pane
+    display:String,
+    /// scanner coordinates in mm, if the orientation of the cfl is known
+    world:Option<[f32;3]>,
+}
 
 impl ViewPanel {
 
-    pub fn update(&mut self, message: ViewPanelMessage) {
+    pub fn update(&mut self, message: ViewPanelMessage) -> Task<ViewPanelMessage> {
+        match message {
+            ViewPanelMessage::Increment => {}
+            ViewPanelMessage::ObliqueToggled(enabled) => {
+                self.panes.retain(|pane| *pane != PaneContent::Oblique);
+                if enabled {
+                    self.panes.push(PaneContent::Oblique);
+                    self.update_oblique();
+                }
+                self.layout_grid();
+            }
+            ViewPanelMessage::ObliqueTheta(theta) => {
+                self.oblique_angles[0] = theta;
+                self.update_oblique();
+            }
+            ViewPanelMessage::ObliquePhi(phi) => {
+                self.oblique_angles[1] = phi;
+                self.update_oblique();
+            }
+            ViewPanelMessage::ObliqueOffset(offset) => {
+                self.oblique_plane.offset = offset;
+                self.update_oblique();
+            }
+            ViewPanelMessage::ProjectionToggled(enabled) => {
+                self.panes.retain(|pane| *pane != PaneContent::Projection);
+                self.projection_buffer = None;
+                if enabled {
+                    self.panes.push(PaneContent::Projection);
+                    self.update_projection();
+                }
+                self.layout_grid();
+            }
+            ViewPanelMessage::ProjectionModeSelected(mode) => {
+                self.projection.mode = mode;
+                self.update_projection();
+            }
+            ViewPanelMessage::ProjectionDimSelected(dim) => {
+                self.projection.dim = dim;
+                self.update_projection();
+            }
+            ViewPanelMessage::LightboxToggled(enabled) => {
+                self.panes.retain(|pane| matches!(pane,PaneContent::Oblique | PaneContent::Projection | PaneContent::Difference));
+                if enabled {
+                    self.update_lightbox();
+                }else {
+                    self.lightbox_data.clear();
+                    self.panes.splice(0..0,self.ortho_panes());
+                }
+                self.layout_grid();
+            }
+            ViewPanelMessage::LightboxDimSelected(dim) => {
+                self.lightbox.dim = dim;
+                self.lightbox.start = 0;
+                if self.lightbox_enabled() {
+                    self.update_lightbox();
+                    self.layout_grid();
+                }
+            }
+            ViewPanelMessage::LightboxStart(start) => {
+                self.lightbox.start = start as usize;
+                if self.lightbox_enabled() {
+                    self.update_lightbox();
+                    self.layout_grid();
+                }
+            }
+            ViewPanelMessage::LightboxStep(step) => {
+                self.lightbox.step = step as usize;
+                if self.lightbox_enabled() {
+                    self.update_lightbox();
+                    self.layout_grid();
+                }
+            }
+            ViewPanelMessage::CinePlayToggled => {
+                self.cine.playing = !self.cine.playing;
+            }
+            ViewPanelMessage::CineDimSelected(dim) => {
+                self.cine.dim = dim;
+            }
+            ViewPanelMessage::CineModeSelected(mode) => {
+                self.cine.mode = mode;
+            }
+            ViewPanelMessage::CineFps(fps) => {
+                self.cine.fps = fps;
+            }
+            ViewPanelMessage::PaneClicked(pane_id,(x,y)) => {
+                if let Some(spec) = self.pane_spec(pane_id) {
+                    let mut idx = spec.cfl_index(&self.pane_source(pane_id).dims,x,y);
+                    if self.panes[pane_id] == PaneContent::Projection {
+                        // the projected dim has no position of its own
+                        idx[self.projection.dim] = self.slice_handler.position()[self.projection.dim];
+                    }
+                    self.slice_handler.set_position(&idx);
+                    self.refresh();
+                }
+            }
+            ViewPanelMessage::PaneHovered(pane_id,pixel) => {
+                match pixel {
+                    Some((x,y)) => self.probe = self.probe_pixel(pane_id,x,y),
+                    // the cursor leaving a pane only clears its own probe, whatever order the panes report in
+                    None => if self.probe.as_ref().is_some_and(|probe| probe.pane_id == pane_id) {
+                        self.probe = None;
+                    }
+                }
+            }
+            ViewPanelMessage::PaneZoomed(pane_id,zoom) => {
+                if self.zoom_linked {
+                    self.pane_zoom.fill(zoom);
+                }else if let Some(z) = self.pane_zoom.get_mut(pane_id) {
+                    *z = zoom;
+                }
+            }
+            ViewPanelMessage::ZoomModeSelected(mode) => {
+                self.zoom_mode = mode;
+                self.pane_zoom.fill(PaneZoom::default());
+            }
+            ViewPanelMessage::ZoomLinkToggled(linked) => {
+                self.zoom_linked = linked;
+            }
+            ViewPanelMessage::SmoothToggled(smooth) => {
+                self.smooth = smooth;
+            }
+            ViewPanelMessage::RoiToolSelected(tool) => {
+                self.roi_tool = tool;
+            }
+            ViewPanelMessage::NoiseRoiToggled(noise) => {
+                self.drawing_noise = noise;
+            }
+            ViewPanelMessage::RoiDrawn(pane_id,roi) => {
+                if self.drawing_noise {
+                    self.noise_roi = Some((pane_id,roi));
+                }else {
+                    self.signal_roi = Some((pane_id,roi));
+                }
+            }
+            ViewPanelMessage::ClearRois => {
+                self.signal_roi = None;
+                self.noise_roi = None;
+            }
+            ViewPanelMessage::LineDrawn(pane_id,from,to) => {
+                self.profile_line = Some((pane_id,from,to));
+                self.profile_along_dim = false;
+                self.update_profile();
+            }
+            ViewPanelMessage::ProfileAlongDimToggled(along_dim) => {
+                self.profile_along_dim = along_dim;
+                self.update_profile();
+            }
+            ViewPanelMessage::ProfileDimSelected(dim) => {
+                self.profile_dim = dim;
+                self.update_profile();
+            }
+            ViewPanelMessage::ProfileComponentToggled(visible,component) => {
+                self.profile_series[component as usize].visible = visible;
+                self.update_profile();
+            }
+            ViewPanelMessage::ClearProfile => {
+                self.profile_line = None;
+                self.profile_along_dim = false;
+                self.update_profile();
+            }
+            ViewPanelMessage::HistogramVolumeToggled(volume) => {
+                self.histogram_volume = volume;
+                self.update_histogram();
+            }
+            ViewPanelMessage::HistogramLogToggled(log) => {
+                self.histogram_plot.log = log;
+                self.update_histogram();
+            }
+            ViewPanelMessage::HistogramHovered(point) => {
+                self.histogram_cursor = point.x;
+            }
+            ViewPanelMessage::HistogramClicked(point) => {
+                self.histogram_cursor = point.x;
+                self.move_window_bound(point.x);
+            }
+            ViewPanelMessage::HistogramDragged(delta) => {
+                self.histogram_cursor = (self.histogram_cursor + delta.x).clamp(0.,1.);
+                self.drag_window_bound(delta.x);
+            }
+            ViewPanelMessage::AutoWindow => {
+                self.set_window(self.histogram_plot.histogram.auto_window());
+                self.update_histogram();
+            }
+            ViewPanelMessage::FftToggled(enabled) => {
+                self.fft_enabled = enabled;
+                return self.start_fft()
+            }
+            ViewPanelMessage::FftInverseToggled(inverse) => {
+                self.fft.inverse = inverse;
+                if self.fft_enabled {
+                    return self.start_fft()
+                }
+            }
+            ViewPanelMessage::FftAxisToggled(dim,transform) => {
+                self.fft.axes[dim] = transform;
+                if self.fft_enabled {
+                    return self.start_fft()
+                }
+            }
+            ViewPanelMessage::FftFinished(generation,data) => {
+                if generation == self.fft_generation {
+                    self.fft_busy = false;
+                    match data {
+                        Ok(data) => {
+                            self.cfl_buffer.data = Arc::unwrap_or_clone(data);
+                            self.reload();
+                        }
+                        Err(e) => self.last_error = Some(e),
+                    }
+                }
+            }
+            ViewPanelMessage::TransformViewSelected(view) => {
+                self.transform_view = view;
+            }
+            ViewPanelMessage::SliceTransformed(view,transform) => {
+                self.slice_handler.transforms[view] = transform;
+                self.update_projection();
+                self.refresh();
+            }
+            ViewPanelMessage::AddCompareClicked => {
+                return Task::perform(pick_cfl_files(),ViewPanelMessage::CompareFilesPicked)
+            }
+            ViewPanelMessage::CompareFilesPicked(paths) => {
+                for path in paths.unwrap_or_default() {
+                    // .mat files often hold many variables, so they're listed to choose from
+                    if path.extension().is_some_and(|ext| ext == "mat") {
+                        match list_mat(&path) {
+                            Ok(variables) => self.mat_file = Some((path,variables)),
+                            Err(e) => self.last_error = Some(format!("couldn't open {}: {:?}",path.display(),e)),
+                        }
+                        continue
+                    }
+                    match CflBuffer::open_all(&path) {
+                        Ok(arrays) => arrays.into_iter().for_each(|(name,cfl_buffer)| self.add_compare(name,cfl_buffer)),
+                        Err(e) => self.last_error = Some(format!("couldn't open {}: {:?}",path.display(),e)),
+                    }
+                }
+            }
+            ViewPanelMessage::MatVariableSelected(variable) => {
+                if let Some((path,_)) = &self.mat_file {
+                    let name = path.file_name().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+                    match read_mat(path,&variable.name) {
+                        Ok(cfl_buffer) => self.add_compare(format!("{}/{}",name,variable.name),cfl_buffer),
+                        Err(e) => self.last_error = Some(format!("couldn't read {}: {:?}",variable.name,e)),
+                    }
+                }
+            }
+            ViewPanelMessage::RawShapeEdited(shape) => {
+                self.raw_shape = shape;
+            }
+            ViewPanelMessage::RawOffsetEdited(offset) => {
+                self.raw_offset = offset;
+            }
+            ViewPanelMessage::RawTypeSelected(dtype) => {
+                self.raw_format.dtype = dtype;
+            }
+            ViewPanelMessage::RawEndiannessSelected(endianness) => {
+                self.raw_format.endianness = endianness;
+            }
+            ViewPanelMessage::RawLayoutSelected(layout) => {
+                self.raw_format.layout = layout;
+            }
+            ViewPanelMessage::OpenRawClicked(primary) => {
+                return Task::perform(pick_raw_files(),move |paths| ViewPanelMessage::RawFilesPicked(primary,paths))
+            }
+            ViewPanelMessage::RawFilesPicked(primary,paths) => {
+                let format = match self.parsed_raw_format() {
+                    Ok(format) => format,
+                    Err(e) => {
+                        self.last_error = Some(format!("invalid raw format: {:?}",e));
+                        return Task::none()
+                    }
+                };
+                for (i,path) in paths.unwrap_or_default().into_iter().enumerate() {
+                    let name = path.file_name().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+                    match format.read(&path) {
+                        Ok(cfl_buffer) if primary && i == 0 => self.set_primary(name,cfl_buffer),
+                        Ok(cfl_buffer) => self.add_compare(name,cfl_buffer),
+                        Err(e) => self.last_error = Some(format!("couldn't open {}: {:?}",path.display(),e)),
+                    }
+                }
+            }
+            ViewPanelMessage::OpenDicomClicked => {
+                return Task::perform(pick_dicom_folder(),ViewPanelMessage::DicomFolderPicked)
+            }
+            ViewPanelMessage::DicomFolderPicked(path) => {
+                if let Some(path) = path {
+                    let name = path.file_name().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+                    match read_dicom_groups(&path) {
+                        Ok(groups) if groups.len() == 1 => self.add_dicom_group(name,&groups[0]),
+                        // folders with localizers or several protocols are listed to choose from
+                        Ok(groups) => self.dicom_groups = Some((name,groups)),
+                        Err(e) => self.last_error = Some(format!("couldn't open {}: {:?}",path.display(),e)),
+                    }
+                }
+            }
+            ViewPanelMessage::DicomGroupSelected(label) => {
+                if let Some((name,groups)) = self.dicom_groups.take() {
+                    if let Some(group) = groups.iter().find(|group| group.to_string() == label) {
+                        self.add_dicom_group(format!("{}/{}",name,label),group);
+                    }
+                    self.dicom_groups = Some((name,groups));
+                }
+            }
+            ViewPanelMessage::ErrorDismissed => {
+                self.last_error = None;
+            }
+            ViewPanelMessage::ClearCompare => {
+                self.mat_file = None;
+                self.dicom_info.retain(|(name,_)| *name == self.name);
+                self.dicom_groups = None;
+                self.compare_files.clear();
+                self.panes.retain(|pane| !matches!(pane,PaneContent::Compare(..) | PaneContent::Difference));
+                self.layout_grid();
+            }
+            ViewPanelMessage::DifferenceToggled(enabled) => {
+                self.panes.retain(|pane| *pane != PaneContent::Difference);
+                if enabled && !self.compare_files.is_empty() {
+                    self.panes.push(PaneContent::Difference);
+                    self.update_difference();
+                    self.difference_limit = self.difference_auto_limit;
+                }
+                self.layout_grid();
+            }
+            ViewPanelMessage::DifferenceModeSelected(mode) => {
+                self.difference_mode = mode;
+                self.update_difference();
+                self.difference_limit = self.difference_auto_limit;
+            }
+            ViewPanelMessage::DifferenceFileSelected(file) => {
+                self.difference_file = file;
+                self.update_difference();
+            }
+            ViewPanelMessage::DifferenceViewSelected(view) => {
+                self.difference_view = view;
+                self.update_difference();
+            }
+            ViewPanelMessage::DifferenceLimit(limit) => {
+                self.difference_limit = limit;
+            }
+            ViewPanelMessage::DifferenceAutoLimit => {
+                self.difference_limit = self.difference_auto_limit;
+            }
+            ViewPanelMessage::ExportFormatSelected(format) => {
+                self.export_format = format;
+            }
+            ViewPanelMessage::ExportPaneSelected(pane_id) => {
+                self.export_pane = pane_id;
+            }
+            ViewPanelMessage::ExportDimSelected(dim) => {
+                self.export_dim = dim;
+            }
+            ViewPanelMessage::AnimationFormatSelected(format) => {
+                self.animation_format = format;
+            }
+            ViewPanelMessage::CflRoiCropToggled(crop) => {
+                self.cfl_roi_crop = crop;
+            }
+            ViewPanelMessage::CflFullDimToggled(dim,full) => {
+                self.cfl_full_dims[dim] = full;
+            }
+            ViewPanelMessage::NiftiComponentsSelected(components) => {
+                self.nifti_components = components;
+            }
+            ViewPanelMessage::ExportClicked(target) => {
+                let extension = match target {
+                    ExportTarget::Grid => ExportFormat::Png.extension(),
+                    ExportTarget::Cine => self.animation_format.extension(),
+                    ExportTarget::Cfl => "cfl",
+                    ExportTarget::Nifti => "nii",
+                    ExportTarget::Npy => "npy",
+                    _ => self.export_format.extension(),
+                };
+                return Task::perform(pick_save_path(extension),move |path| ViewPanelMessage::ExportPathPicked(target,path))
+            }
+            ViewPanelMessage::ExportPathPicked(target,Some(path)) => {
+                let result = match target {
+                    ExportTarget::Pane => self.export_pane(&path),
+                    ExportTarget::Batch => self.export_batch(&path),
+                    ExportTarget::Cine => self.export_cine(&path),
+                    ExportTarget::Cfl => self.export_cfl(&path),
+                    ExportTarget::Nifti => write_nifti(&path,&self.cfl_buffer,self.nifti_components),
+                    ExportTarget::Npy => write_npy(&path,&self.cfl_buffer),
+                    ExportTarget::Grid => {
+                        return iced::window::latest()
+                            .and_then(iced::window::screenshot)
+                            .map(move |screenshot| ViewPanelMessage::GridCaptured(path.clone(),screenshot))
+                    }
+                };
+                if let Err(e) = result {
+                    self.last_error = Some(format!("export failed: {:?}",e));
+                }
+            }
+            ViewPanelMessage::ExportPathPicked(_,None) => {}
+            ViewPanelMessage::GridCaptured(path,screenshot) => {
+                if let Err(e) = self.export_grid(&path,&screenshot) {
+                    self.last_error = Some(format!("export failed: {:?}",e));
+                }
+            }
+            ViewPanelMessage::CineTick => {
+                let dim = self.cine.dim;
+                let n = self.cfl_buffer.dims.shape()[dim];
+                let next = self.cine.next_frame(self.current_index(dim),n);
+                self.set_index(dim,next);
+            }
+        }
+        Task::none()
+    }
+
+    pub fn subscription(&self) -> Subscription<ViewPanelMessage> {
+        if self.cine.playing {
+            iced::time::every(self.cine.interval()).map(|_| ViewPanelMessage::CineTick)
+        }else {
+            Subscription::none()
+        }
+    }
+
+    pub fn view(&self) -> Element<'_, ViewPanelMessage> {
+        if self.profile_visible() {
+            row![
+                self.controls(),
+                column![
+                    container(self.pane_grid()).height(Length::FillPortion(3)),
+                    container(self.profile_plot()).height(Length::FillPortion(1)).padding(10),
+                ],
+            ].into()
+        }else {
+            row![self.controls(),self.pane_grid()].into()
+        }
+    }
+
+    fn profile_plot(&self) -> Element<'_, ViewPanelMessage> {
+        let mut chart = Chart::new(&self.profile_chart);
+        for (series,component) in self.profile_series.iter().zip(ProfileComponent::ALL) {
+            chart = chart.plot_data(series,PROFILE_X_ID,component.axis_id());
+        }
+        row![
+            container(chart).width(Length::Fill).height(Length::Fill),
+            column(ProfileComponent::ALL.map(|component|{
+                toggler(self.profile_series[component as usize].visible)
+                    .label(component.to_string())
+                    .on_toggle(move |visible| ViewPanelMessage::ProfileComponentToggled(visible,component))
+                    .into()
+            })).spacing(5),
+        ].spacing(10).into()
+    }
+
+    fn histogram(&self) -> Element<'_, ViewPanelMessage> {
+        let chart = Chart::new(&self.histogram_chart)
+            .plot_data(&self.histogram_plot,HISTOGRAM_X_ID,HISTOGRAM_Y_ID)
+            .on_hover(ViewPanelMessage::HistogramHovered)
+            .on_click(ViewPanelMessage::HistogramClicked)
+            // drag deltas are normalized to the plot area like the hover and click points, the same units
+            // chart states pan by
+            .on_drag(ViewPanelMessage::HistogramDragged);
+        column![
+            container(chart).width(Length::Fill).height(Length::Fixed(150.)),
+            text(format!(
+                "window: {:.3e} to {:.3e}\nlevel: {:.3e} width: {:.3e}",
+                self.window.lo,self.window.hi,self.window.level(),self.window.width()
+            )),
+            row![
+                toggler(self.histogram_volume).label("volume").on_toggle(ViewPanelMessage::HistogramVolumeToggled),
+                toggler(self.histogram_plot.log).label("log").on_toggle(ViewPanelMessage::HistogramLogToggled),
+            ].spacing(5),
+            button("auto window").on_press(ViewPanelMessage::AutoWindow),
+        ].spacing(5).into()
+    }
+
+    fn difference_controls(&self) -> Element<'_, ViewPanelMessage> {
+        if self.compare_files.is_empty() {
+            return column![].into()
+        }
+        let max_limit = self.difference_auto_limit * 4.;
+        column![
+            toggler(self.panes.contains(&PaneContent::Difference)).label("difference").on_toggle(ViewPanelMessage::DifferenceToggled),
+            pick_list(DifferenceMode::ALL,Some(self.difference_mode),ViewPanelMessage::DifferenceModeSelected),
+            row![
+                text("B"),
+                pick_list((0..self.compare_files.len()).collect::<Vec<_>>(),Some(self.difference_file),ViewPanelMessage::DifferenceFileSelected),
+                text("view"),
+                pick_list([0,1,2],Some(self.difference_view),ViewPanelMessage::DifferenceViewSelected),
+            ].spacing(5),
+            text(format!("range: ±{:.3e}",self.difference_limit)),
+            slider(0.0..=max_limit,self.difference_limit,ViewPanelMessage::DifferenceLimit).step(max_limit / 200.),
+            button("auto range").on_press(ViewPanelMessage::DifferenceAutoLimit),
+        ].spacing(5).into()
+    }
+
+    fn error_controls(&self) -> Element<'_, ViewPanelMessage> {
+        let Some(error) = &self.last_error else {
+            return column![].into()
+        };
+        column![
+            text(error).color(Color::from_rgb(1.,0.4,0.4)),
+            button("dismiss").on_press(ViewPanelMessage::ErrorDismissed),
+        ].spacing(5).into()
+    }
+
+    fn mat_controls(&self) -> Element<'_, ViewPanelMessage> {
+        let Some((path,variables)) = &self.mat_file else {
+            return column![].into()
+        };
+        let name = path.file_name().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+        column![
+            text(name),
+            pick_list(variables.as_slice(),None::<MatVariable>,ViewPanelMessage::MatVariableSelected).placeholder("load variable"),
+        ].spacing(5).into()
+    }
 
+    fn raw_controls(&self) -> Element<'_, ViewPanelMessage> {
+        let size = match self.parsed_raw_format().and_then(|format| Ok((format.offset,format.data_size()?))) {
+            Ok((offset,size)) => format!("{} + {} bytes",offset,size),
+            Err(_) => "invalid shape or offset".to_string(),
+        };
+        column![
+            text_input("shape, e.g. 256,256,64",&self.raw_shape).on_input(ViewPanelMessage::RawShapeEdited),
+            row![
+                pick_list(RawType::ALL,Some(self.raw_format.dtype),ViewPanelMessage::RawTypeSelected),
+                pick_list(RawLayout::ALL,Some(self.raw_format.layout),ViewPanelMessage::RawLayoutSelected),
+            ].spacing(5),
+            pick_list(Endianness::ALL,Some(self.raw_format.endianness),ViewPanelMessage::RawEndiannessSelected),
+            row![
+                text("offset"),
+                text_input("bytes",&self.raw_offset).on_input(ViewPanelMessage::RawOffsetEdited),
+            ].spacing(5),
+            text(size),
+            row![
+                button("open raw").on_press(ViewPanelMessage::OpenRawClicked(true)),
+                button("compare raw").on_press(ViewPanelMessage::OpenRawClicked(false)),
+            ].spacing(5),
+        ].spacing(5).into()
     }
 
-    pub fn view(&self) -> Element<ViewPanelMessage> {
-        
+    fn dicom_controls(&self) -> Element<'_, ViewPanelMessage> {
+        let info = self.dicom_info.iter().map(|(name,info)| text(format!("{}\n{}",name,info)).into());
+        let groups:Element<'_, ViewPanelMessage> = match &self.dicom_groups {
+            Some((name,groups)) => {
+                let labels:Vec<String> = groups.iter().map(|group| group.to_string()).collect();
+                column![
+                    text(name),
+                    pick_list(labels,None::<String>,ViewPanelMessage::DicomGroupSelected).placeholder("load series"),
+                ].spacing(5).into()
+            }
+            None => column![].into(),
+        };
+        column![
+            button("compare dicom").on_press(ViewPanelMessage::OpenDicomClicked),
+            groups,
+            column(info).spacing(5),
+        ].spacing(5).into()
+    }
+
+    fn export_controls(&self) -> Element<'_, ViewPanelMessage> {
+        column![
+            pick_list(ExportFormat::ALL,Some(self.export_format),ViewPanelMessage::ExportFormatSelected),
+            row![
+                text("pane"),
+                pick_list((0..self.panes.len()).collect::<Vec<_>>(),Some(self.export_pane),ViewPanelMessage::ExportPaneSelected),
+                button("export pane").on_press(ViewPanelMessage::ExportClicked(ExportTarget::Pane)),
+            ].spacing(5),
+            row![
+                text("dim"),
+                pick_list((0..N_DIMS).collect::<Vec<_>>(),Some(self.export_dim),ViewPanelMessage::ExportDimSelected),
+                button("export all").on_press(ViewPanelMessage::ExportClicked(ExportTarget::Batch)),
+            ].spacing(5),
+            button("export grid").on_press(ViewPanelMessage::ExportClicked(ExportTarget::Grid)),
+            self.cfl_controls(),
+        ].spacing(5).into()
+    }
+
+    fn cfl_controls(&self) -> Element<'_, ViewPanelMessage> {
+        let shape = self.pane_source(self.export_pane).dims.shape();
+        let plane = self.pane_spec(self.export_pane).map(|spec| [spec.row_dim,spec.col_dim]).unwrap_or_default();
+        let dims = (0..N_DIMS).filter(|dim| shape[*dim] > 1 && !plane.contains(dim)).map(|dim|{
+            checkbox(self.cfl_full_dims[dim])
+                .label(dim.to_string())
+                .on_toggle(move |full| ViewPanelMessage::CflFullDimToggled(dim,full))
+                .into()
+        });
+        column![
+            toggler(self.cfl_roi_crop).label("crop to signal roi").on_toggle(ViewPanelMessage::CflRoiCropToggled),
+            text("whole dims"),
+            row(dims).spacing(5).wrap(),
+            button("export cfl").on_press(ViewPanelMessage::ExportClicked(ExportTarget::Cfl)),
+            row![
+                pick_list(NiftiComponents::ALL,Some(self.nifti_components),ViewPanelMessage::NiftiComponentsSelected),
+                button("export nifti").on_press(ViewPanelMessage::ExportClicked(ExportTarget::Nifti)),
+            ].spacing(5),
+            button("export npy").on_press(ViewPanelMessage::ExportClicked(ExportTarget::Npy)),
+        ].spacing(5).into()
+    }
+
+    fn fft_controls(&self) -> Element<'_, ViewPanelMessage> {
+        let shape = self.cfl_buffer.dims.shape();
+        let axes = (0..N_DIMS).filter(|&dim| shape[dim] > 1).map(|dim|{
+            checkbox(self.fft.axes[dim])
+                .label(dim.to_string())
+                .on_toggle(move |transform| ViewPanelMessage::FftAxisToggled(dim,transform))
+                .into()
+        });
+        column![
+            row![
+                toggler(self.fft_enabled).label("fft").on_toggle(ViewPanelMessage::FftToggled),
+                toggler(self.fft.inverse).label("inverse").on_toggle(ViewPanelMessage::FftInverseToggled),
+            ].spacing(5),
+            row(axes).spacing(5),
+            text(if self.fft_busy { "transforming..." } else { "" }),
+        ].spacing(5).into()
+    }
+
+    fn transform_controls(&self) -> Element<'_, ViewPanelMessage> {
+        let view = self.transform_view;
+        let t = self.slice_handler.transforms[view];
+        let transformed = move |t:SliceTransform| ViewPanelMessage::SliceTransformed(view,t);
+        column![
+            row![
+                text("view"),
+                pick_list([0,1,2],Some(view),ViewPanelMessage::TransformViewSelected),
+                button("reset").on_press(transformed(SliceTransform::default())),
+            ].spacing(5),
+            row![
+                button("flip h").on_press(transformed(t.flipped_x())),
+                button("flip v").on_press(transformed(t.flipped_y())),
+                button("transpose").on_press(transformed(t.transposed())),
+            ].spacing(5),
+            row![
+                button("rotate ⟲").on_press(transformed(t.rotated_ccw())),
+                button("rotate ⟳").on_press(transformed(t.rotated_cw())),
+            ].spacing(5),
+            row![
+                pick_list(AxisShift::ALL,Some(t.shift_x),move |shift| transformed(SliceTransform { shift_x: shift, ..t })),
+                pick_list(AxisShift::ALL,Some(t.shift_y),move |shift| transformed(SliceTransform { shift_y: shift, ..t })),
+            ].spacing(5),
+        ].spacing(5).into()
+    }
+
+    fn controls(&self) -> Element<'_, ViewPanelMessage> {
+        let oblique_enabled = self.panes.contains(&PaneContent::Oblique);
+        let max_offset = self.oblique_plane.slice_dims(&self.cfl_buffer.dims).shape()[0] as f32 * self.oblique_plane.step() / 2.;
+        let lightbox_len = self.cfl_buffer.dims.shape().get(self.lightbox.dim).copied().unwrap_or(1) as u32;
+        let controls = column![
+            self.error_controls(),
+            text(self.probe_text()),
+            row![
+                button("compare cfl").on_press(ViewPanelMessage::AddCompareClicked),
+                button("clear").on_press(ViewPanelMessage::ClearCompare),
+            ].spacing(5),
+            self.mat_controls(),
+            self.raw_controls(),
+            self.dicom_controls(),
+            self.difference_controls(),
+            self.histogram(),
+            self.fft_controls(),
+            self.transform_controls(),
+            self.export_controls(),
+            row![
+                button("fit").on_press(ViewPanelMessage::ZoomModeSelected(ZoomMode::Fit)),
+                button("1:1").on_press(ViewPanelMessage::ZoomModeSelected(ZoomMode::Native)),
+            ].spacing(5),
+            toggler(self.zoom_linked).label("link zoom").on_toggle(ViewPanelMessage::ZoomLinkToggled),
+            toggler(self.smooth).label("bilinear").on_toggle(ViewPanelMessage::SmoothToggled),
+            row![
+                pick_list(RoiTool::ALL,Some(self.roi_tool),ViewPanelMessage::RoiToolSelected),
+                button("clear").on_press(ViewPanelMessage::ClearRois),
+            ].spacing(5),
+            toggler(self.drawing_noise).label("draw noise roi").on_toggle(ViewPanelMessage::NoiseRoiToggled),
+            text(self.roi_text()),
+            row![
+                toggler(self.profile_along_dim).label("profile along dim").on_toggle(ViewPanelMessage::ProfileAlongDimToggled),
+                pick_list((0..N_DIMS).collect::<Vec<_>>(),Some(self.profile_dim),ViewPanelMessage::ProfileDimSelected),
+            ].spacing(5),
+            button("clear profile").on_press(ViewPanelMessage::ClearProfile),
+            toggler(oblique_enabled).label("oblique").on_toggle(ViewPanelMessage::ObliqueToggled),
+            text(format!("theta: {:.1}",self.oblique_angles[0])),
+            slider(0.0..=180.0,self.oblique_angles[0],ViewPanelMessage::ObliqueTheta).step(0.5),
+            text(format!("phi: {:.1}",self.oblique_angles[1])),
+            slider(-180.0..=180.0,self.oblique_angles[1],ViewPanelMessage::ObliquePhi).step(0.5),
+            text(format!("offset: {:.1}",self.oblique_plane.offset)),
+            slider(-max_offset..=max_offset,self.oblique_plane.offset,ViewPanelMessage::ObliqueOffset).step(0.5),
+            toggler(self.panes.contains(&PaneContent::Projection)).label("projection").on_toggle(ViewPanelMessage::ProjectionToggled),
+            row![
+                pick_list(ProjectionMode::ALL,Some(self.projection.mode),ViewPanelMessage::ProjectionModeSelected),
+                pick_list((0..N_DIMS).collect::<Vec<_>>(),Some(self.projection.dim),ViewPanelMessage::ProjectionDimSelected),
+            ].spacing(5),
+            toggler(self.lightbox_enabled()).label("lightbox").on_toggle(ViewPanelMessage::LightboxToggled),
+            pick_list((0..N_DIMS).collect::<Vec<_>>(),Some(self.lightbox.dim),ViewPanelMessage::LightboxDimSelected),
+            text(format!("start: {}",self.lightbox.start)),
+            slider(0..=lightbox_len.saturating_sub(1),self.lightbox.start as u32,ViewPanelMessage::LightboxStart),
+            text(format!("step: {}",self.lightbox.step)),
+            slider(1..=lightbox_len.max(1),self.lightbox.step as u32,ViewPanelMessage::LightboxStep),
+            text(if self.lightbox_total > self.lightbox_data.len() {
+                format!("showing {} of {} slices",self.lightbox_data.len(),self.lightbox_total)
+            }else {
+                format!("slices: {}",self.lightbox_total)
+            }),
+            text(format!("cine frame: {}",self.current_index(self.cine.dim))),
+            row![
+                button(if self.cine.playing { "pause" } else { "play" }).on_press(ViewPanelMessage::CinePlayToggled),
+                pick_list((0..N_DIMS).collect::<Vec<_>>(),Some(self.cine.dim),ViewPanelMessage::CineDimSelected),
+                pick_list(CineMode::ALL,Some(self.cine.mode),ViewPanelMessage::CineModeSelected),
+            ].spacing(5),
+            text(format!("fps: {:.0}",self.cine.fps)),
+            slider(1.0..=60.0,self.cine.fps,ViewPanelMessage::CineFps),
+            row![
+                pick_list(AnimationFormat::ALL,Some(self.animation_format),ViewPanelMessage::AnimationFormatSelected),
+                button("export cine").on_press(ViewPanelMessage::ExportClicked(ExportTarget::Cine)),
+            ].spacing(5),
+        ].spacing(10).padding(10);
+        // the controls outgrow small windows
+        scrollable(controls).width(Length::Fixed(CONTROLS_WIDTH)).into()
+    }
+
+    fn pane_grid(&self) -> Element<'_, ViewPanelMessage> {
+
         let mut r = column![];
 
         let mut pane_id = 0;
@@ -61,12 +1255,26 @@ impl ViewPanel {
             let mut c = row![];
             for _ in 0..self.grid_dims[1] {
                 let (bytes,dims) = self.update_pane(pane_id);
+                let image_size = [dims.shape()[0],dims.shape()[1]];
+                let crosshair = self.pane_crosshair(pane_id);
+                let pane = PaneCanvas {
+                    pane_id,
+                    handle: Handle::from_rgba(image_size[0] as u32,image_size[1] as u32,bytes),
+                    image_size,
+                    crosshair,
+                    zoom: self.pane_zoom.get(pane_id).copied().unwrap_or_default(),
+                    zoom_mode: self.zoom_mode,
+                    filter_method: if self.smooth { FilterMethod::Linear } else { FilterMethod::Nearest },
+                    pixel_size: self.pane_pixel_size(pane_id),
+                    roi_tool: self.roi_tool,
+                    rois: self.pane_rois(pane_id),
+                    profile_line: self.profile_line.filter(|(id,..)| *id == pane_id).map(|(_,from,to)| (from,to)),
+                    label: self.pane_label(pane_id),
+                };
                 pane_id += 1;
                 c = c.push(
                     container(
-                        Image::new(
-                            Handle::from_rgba(dims.shape()[0] as u32,dims.shape()[1] as u32,bytes)
-                        )
+                        Canvas::new(pane).width(Length::Fill).height(Length::Fill)
                     ).width(Length::FillPortion(1)).padding(10)
                 );
             }
@@ -81,15 +1289,723 @@ impl ViewPanel {
 
     /// returns rgba image bytes for a single pane
     fn update_pane(&self, pane_id:usize) -> (Vec<u8>, ArrayDim) {
-        let cfl_data = vec![Complex32::ONE;self.pane_dims[0] * self.pane_dims[1]];
-        let bytes = make_rgba(&cfl_data,self.view_mode,self.scaling);
-        (bytes,ArrayDim::from_shape(&[self.pane_dims[0],self.pane_dims[1]]))
+        if let Some((cfl_data,dims)) = self.pane_data(pane_id) {
+            let bytes = self.pane_rgba(pane_id,cfl_data);
+            (bytes,dims)
+        }else {
+            let cfl_data = vec![Complex32::ZERO;self.pane_dims[0] * self.pane_dims[1]];
+            let bytes = make_rgba(&cfl_data,self.view_mode,&self.window);
+            (bytes,ArrayDim::from_shape(&[self.pane_dims[0],self.pane_dims[1]]))
+        }
+    }
+
+    /// renders slice data with the colormap of a pane
+    fn pane_rgba(&self, pane_id:usize, cfl_data:&[Complex32]) -> Vec<u8> {
+        if self.panes.get(pane_id) == Some(&PaneContent::Difference) {
+            let values:Vec<f32> = cfl_data.iter().map(|x| self.difference_scalar(*x)).collect();
+            signed_rgba(&values,self.difference_mode.center(),self.difference_limit)
+        }else {
+            make_rgba(cfl_data,self.view_mode,&self.window)
+        }
+    }
+
+    /// the real value a sample of a pane is displayed with
+    fn pane_scalar(&self, pane_id:usize, x:Complex32) -> f32 {
+        if self.panes.get(pane_id) == Some(&PaneContent::Difference) {
+            self.difference_scalar(x)
+        }else {
+            scalar(x,self.view_mode)
+        }
+    }
+
+    /// pixel of a pane the crosshair passes through
+    fn pane_crosshair(&self, pane_id:usize) -> Option<(usize,usize)> {
+        self.pane_spec(pane_id)
+            .map(|spec| spec.pixel(&self.pane_source(pane_id).dims,&self.slice_handler.position()))
+    }
+
+    /// the slice data currently shown in a pane
+    fn pane_data(&self, pane_id:usize) -> Option<(&[Complex32],ArrayDim)> {
+        match self.panes.get(pane_id)? {
+            PaneContent::Ortho(view) => Some(self.slice_handler.slice_view(*view)),
+            PaneContent::Oblique => Some((self.oblique_data.as_slice(),self.oblique_dims)),
+            PaneContent::Projection => Some((self.projection_data.as_slice(),self.projection_dims)),
+            PaneContent::Lightbox(i) => self.lightbox_data.get(*i).map(|(data,dims)| (data.as_slice(),*dims)),
+            PaneContent::Compare(file,view) => self.compare_files.get(*file)
+                .map(|compare| (compare.slices[*view].0.as_slice(),compare.slices[*view].1)),
+            PaneContent::Difference => Some((self.difference_data.as_slice(),self.difference_dims)),
+        }
+    }
+
+    /// the regions drawn on a pane with their outline colors
+    fn pane_rois(&self, pane_id:usize) -> Vec<(RoiShape,Color)> {
+        let signal = self.signal_roi.iter().map(|roi| (roi,Color::from_rgb(0.,1.,0.)));
+        let noise = self.noise_roi.iter().map(|roi| (roi,Color::from_rgb(1.,0.,0.)));
+        signal.chain(noise)
+            .filter(|((id,_),_)| *id == pane_id)
+            .map(|((_,roi),color)| (roi.clone(),color))
+            .collect()
+    }
+
+    /// statistics of a region over the slice currently shown in its pane
+    fn roi_stats(&self, roi:&Option<(usize,RoiShape)>) -> Option<RoiStats> {
+        let (pane_id,roi) = roi.as_ref()?;
+        let (data,dims) = self.pane_data(*pane_id)?;
+        RoiStats::new(roi,data,&dims)
+    }
+
+    fn roi_text(&self) -> String {
+        let signal = self.roi_stats(&self.signal_roi);
+        let noise = self.roi_stats(&self.noise_roi);
+        let mut lines = vec![];
+        if let Some(signal) = &signal {
+            lines.push(format!("signal roi\n{}",signal));
+        }
+        if let Some(noise) = &noise {
+            lines.push(format!("noise roi\n{}",noise));
+        }
+        if let (Some(signal),Some(noise)) = (&signal,&noise) {
+            match signal.snr(noise) {
+                Some(snr) => lines.push(format!("snr: {:.2}",snr)),
+                None => lines.push("snr: n/a".to_string()),
+            }
+        }
+        lines.join("\n")
+    }
+
+    /// the slice spec a pane is extracted with, if it shows an axis-aligned plane
+    fn pane_spec(&self, pane_id:usize) -> Option<SliceSpec> {
+        match self.panes.get(pane_id)? {
+            PaneContent::Ortho(view) => Some(self.slice_handler.spec(*view)),
+            PaneContent::Lightbox(i) if *i < self.lightbox_data.len() => Some(self.lightbox.spec_at(*i)),
+            PaneContent::Compare(file,view) if *file < self.compare_files.len() => Some(self.compare_spec(*file,*view)),
+            PaneContent::Difference => Some(self.slice_handler.spec(self.difference_view)),
+            PaneContent::Projection => self.projection_buffer.as_ref().map(|_|{
+                let mut spec = self.slice_handler.spec(self.projection_view());
+                spec.fixed[self.projection.dim] = 0;
+                spec
+            }),
+            _ => None,
+        }
+    }
+
+    /// looks up the value under a pixel of a pane
+    fn probe_pixel(&self, pane_id:usize, x:usize, y:usize) -> Option<Probe> {
+        if self.panes.get(pane_id) == Some(&PaneContent::Difference) {
+            let spec = self.pane_spec(pane_id)?;
+            let width = self.difference_dims.shape()[0];
+            let value = *self.difference_data.get(y * width + x)?;
+            let idx = spec.cfl_index(&self.cfl_buffer.dims,x,y);
+            let display = format!("{:.4e} ({})",self.difference_scalar(value),self.difference_mode);
+            return Some(Probe { pane_id, index: Some(idx), value, display, world: self.cfl_buffer.world_position(&idx) })
+        }
+        let (index,value) = match self.pane_spec(pane_id) {
+            Some(spec) => {
+                let idx = spec.cfl_index(&self.pane_source(pane_id).dims,x,y);
+                (Some(idx),self.pane_source(pane_id).get(&idx)?)
+            }
+            None => {
+                let width = self.oblique_dims.shape()[0];
+                (None,*self.oblique_data.get(y * width + x)?)
+            }
+        };
+        let display = self.window.display_value(scalar(value,self.view_mode)).to_string();
+        let world = index.and_then(|idx| self.pane_source(pane_id).world_position(&idx));
+        Some(Probe { pane_id, index, value, display, world })
+    }
+
+    fn probe_text(&self) -> String {
+        let Some(probe) = &self.probe else {
+            return "hover a pane to probe".to_string()
+        };
+        let index = match probe.index {
+            Some(idx) => format!("{:?}",idx),
+            None => "oblique".to_string(),
+        };
+        let world = match probe.world {
+            Some([x,y,z]) => format!("\nmm: {:.1} {:.1} {:.1}",x,y,z),
+            None => String::new(),
+        };
+        format!(
+            "index: {}{}\nre: {:.4e}\nim: {:.4e}\n|z|: {:.4e}\n∠z: {:.4}\ndisplay: {}",
+            index,world,probe.value.re,probe.value.im,probe.value.norm(),probe.value.arg(),probe.display
+        )
+    }
+
+    /// physical size of the image pixels of a pane in mm, if the geometry of the cfl it shows is known
+    fn pane_pixel_size(&self, pane_id:usize) -> Option<[f32;2]> {
+        if self.panes.get(pane_id) == Some(&PaneContent::Oblique) {
+            return self.cfl_buffer.geometry_known.then(|| [self.oblique_plane.step();2])
+        }
+        let (h,v) = self.pane_spec(pane_id)?.output_axes();
+        let source = self.pane_source(pane_id);
+        source.geometry_known.then(|| [source.voxel_size[h],source.voxel_size[v]])
+    }
+
+    /// the cfl buffer a pane's slice is extracted from
+    fn pane_source(&self, pane_id:usize) -> &CflBuffer {
+        match (self.panes.get(pane_id),self.projection_buffer.as_ref()) {
+            (Some(PaneContent::Projection),Some(buffer)) => buffer,
+            (Some(PaneContent::Compare(file,_)),_) if *file < self.compare_files.len() => &self.compare_files[*file].cfl_buffer,
+            _ => &self.cfl_buffer,
+        }
     }
 
     fn n_panes(&self) -> usize {
         self.grid_dims[0] * self.grid_dims[1]
     }
 
+    /// the slice index currently shown along a cfl dimension
+    fn current_index(&self, dim:usize) -> usize {
+        match self.slice_handler.view_slices.iter().position(|&d| d == dim) {
+            Some(view) => self.slice_handler.slice_indices[view],
+            None => self.slice_handler.fixed[dim],
+        }
+    }
+
+    /// moves every pane to a new slice index along a cfl dimension
+    fn set_index(&mut self, dim:usize, index:usize) {
+        let index = index.min(self.cfl_buffer.dims.shape()[dim].saturating_sub(1));
+        match self.slice_handler.view_slices.iter().position(|&d| d == dim) {
+            Some(view) => self.slice_handler.slice_indices[view] = index,
+            None => self.slice_handler.fixed[dim] = index,
+        }
+        self.refresh();
+    }
+
+    fn set_window(&mut self, window:Window) {
+        self.window = window;
+        self.histogram_plot.window = window;
+    }
+
+    /// moves the window bound closest to a normalized position on the histogram chart, which is then the
+    /// bound dragged
+    fn move_window_bound(&mut self, x:f32) {
+        let [start,end] = self.histogram_plot.domain;
+        let value = start + x.clamp(0.,1.) * (end - start);
+        self.histogram_drag_hi = (value - self.window.lo).abs() >= (value - self.window.hi).abs();
+        let window = if self.histogram_drag_hi {
+            Window::new(self.window.lo,value)
+        }else {
+            Window::new(value,self.window.hi)
+        };
+        self.set_window(window);
+    }
+
+    /// shifts the dragged window bound by a normalized distance along the histogram chart, keeping it on the
+    /// chart
+    fn drag_window_bound(&mut self, dx:f32) {
+        let [start,end] = self.histogram_plot.domain;
+        let shift = dx * (end - start);
+        let (moved,fixed) = if self.histogram_drag_hi {
+            (self.window.hi + shift,self.window.lo)
+        }else {
+            (self.window.lo + shift,self.window.hi)
+        };
+        let moved = moved.clamp(start.min(fixed),end.max(fixed));
+        // the bound keeps being dragged after it passes the other one
+        self.histogram_drag_hi = moved > fixed;
+        self.set_window(Window::new(moved,fixed));
+    }
+
+    /// bins the displayed values of the first pane, or of the whole cfl, and updates the chart bounds
+    fn update_histogram(&mut self) {
+        let view_mode = self.view_mode;
+        let values:Vec<f32> = if self.histogram_volume {
+            self.cfl_buffer.data.par_iter().map(|x| scalar(*x,view_mode)).collect()
+        }else {
+            self.pane_data(0)
+                .map(|(data,_)| data.par_iter().map(|x| scalar(*x,view_mode)).collect())
+                .unwrap_or_default()
+        };
+        let histogram = Histogram::new(&values,DEFAULT_BINS);
+        // keep the window markers in view
+        let domain = [histogram.min.min(self.window.lo),histogram.max.max(self.window.hi)];
+        self.histogram_plot.histogram = histogram;
+        self.histogram_plot.domain = domain;
+        self.histogram_chart.set_domain(&HISTOGRAM_X_ID,domain[0] as f64,domain[1] as f64);
+        self.histogram_chart.set_domain(&HISTOGRAM_Y_ID,0.,self.histogram_plot.max_height() * 1.05);
+    }
+
+    fn profile_visible(&self) -> bool {
+        self.profile_along_dim || self.profile_line.is_some()
+    }
+
+    /// samples the profile and updates the chart series and bounds
+    fn update_profile(&mut self) {
+        let samples:Vec<(f32,Complex32)> = if self.profile_along_dim {
+            dim_profile(&self.cfl_buffer,&self.slice_handler.position(),self.profile_dim)
+                .map(|s| s.into_iter().enumerate().map(|(i,x)| (i as f32,x)).collect())
+                .unwrap_or_default()
+        }else if let Some((pane_id,from,to)) = self.profile_line {
+            self.pane_data(pane_id)
+                .map(|(data,dims)| line_profile(data,&dims,from,to))
+                .unwrap_or_default()
+        }else {
+            vec![]
+        };
+
+        for (series,component) in self.profile_series.iter_mut().zip(ProfileComponent::ALL) {
+            series.points = samples.iter()
+                .map(|(t,x)| PlotPoint::new(*t as f64,component.value(*x) as f64))
+                .collect();
+        }
+
+        let x_max = samples.last().map(|(t,_)| *t as f64).unwrap_or(1.).max(1.);
+        let (y_min,y_max) = self.profile_series[0..3].iter()
+            .filter(|series| series.visible)
+            .flat_map(|series| series.points.iter().map(|p| p.y))
+            .fold((0f64,0f64),|(lo,hi),y| (lo.min(y),hi.max(y)));
+        let y_max = if y_max > y_min { y_max } else { y_min + 1. };
+        self.profile_chart.set_domain(&PROFILE_X_ID,0.,x_max);
+        self.profile_chart.set_domain(&PROFILE_Y_ID,y_min,y_max);
+        self.profile_chart.set_domain(&PROFILE_PHASE_ID,-std::f64::consts::PI,std::f64::consts::PI);
+    }
+
+    /// re-extracts the data of every pane after the slice indices change
+    fn refresh(&mut self) {
+        if let Err(e) = self.slice_handler.update_all(&self.cfl_buffer) {
+            self.last_error = Some(format!("failed to update slices: {:?}",e));
+        }
+        if self.panes.contains(&PaneContent::Oblique) {
+            self.update_oblique();
+        }
+        self.update_projection_slice();
+        if self.lightbox_enabled() {
+            self.update_lightbox();
+        }
+        if self.profile_visible() {
+            self.update_profile();
+        }
+        self.update_compare();
+        self.update_difference();
+        if !self.histogram_volume {
+            self.update_histogram();
+        }
+    }
+
+    /// the raw format with the shape and offset typed into the raw controls
+    fn parsed_raw_format(&self) -> Result<RawFormat,ViewError> {
+        let shape = parse_shape(self.raw_shape.split(','))?;
+        let offset = self.raw_offset.trim().parse().map_err(|_| ViewError::Parse(self.raw_offset.clone()))?;
+        Ok(RawFormat { shape, offset, ..self.raw_format })
+    }
+
+    /// label of the cfl shown on the primary panes
+    pub fn set_name(&mut self, name:impl Into<String>) {
+        self.name = name.into();
+    }
+
+    /// replaces the primary cfl, starting from the default view. Comparison files, dicom tags and the raw
+    /// format are kept
+    pub fn set_primary(&mut self, name:impl Into<String>, cfl_buffer:CflBuffer) {
+        let mut view_panel = ViewPanel::from(cfl_buffer);
+        view_panel.set_name(name);
+        view_panel.raw_format = self.raw_format;
+        view_panel.raw_shape = std::mem::take(&mut self.raw_shape);
+        view_panel.raw_offset = std::mem::take(&mut self.raw_offset);
+        view_panel.dicom_info = std::mem::take(&mut self.dicom_info);
+        for compare in std::mem::take(&mut self.compare_files) {
+            view_panel.add_compare(compare.name,compare.cfl_buffer);
+        }
+        *self = view_panel;
+    }
+
+    /// stacks a group of dicom images and opens it for comparison with its tags
+    fn add_dicom_group(&mut self, name:String, group:&DicomGroup) {
+        match group.stack() {
+            Ok((cfl_buffer,info)) => {
+                self.add_dicom_info(name.clone(),info);
+                self.add_compare(name,cfl_buffer);
+            }
+            Err(e) => self.last_error = Some(format!("couldn't stack {}: {:?}",name,e)),
+        }
+    }
+
+    /// shows the tags of a dicom series opened under `name` with the controls
+    pub fn add_dicom_info(&mut self, name:impl Into<String>, info:DicomInfo) {
+        self.dicom_info.push((name.into(),info));
+    }
+
+    /// opens another cfl next to the primary one, adding a row of orthogonal views for it
+    pub fn add_compare(&mut self, name:impl Into<String>, cfl_buffer:CflBuffer) {
+        let file = self.compare_files.len();
+        self.compare_files.push(CompareFile {
+            name: name.into(),
+            cfl_buffer,
+            slices: std::array::from_fn(|_| (vec![],ArrayDim::from_shape(&[0,0]))),
+        });
+        if !self.lightbox_enabled() {
+            let at = self.panes.iter().take_while(|pane| matches!(pane,PaneContent::Ortho(_) | PaneContent::Compare(..))).count();
+            self.panes.splice(at..at,(0..3).map(|view| PaneContent::Compare(file,view)));
+        }
+        self.update_compare();
+        self.layout_grid();
+    }
+
+    /// the orthogonal views of the primary cfl followed by those of the comparison files
+    fn ortho_panes(&self) -> Vec<PaneContent> {
+        let compare = (0..self.compare_files.len()).flat_map(|file| (0..3).map(move |view| PaneContent::Compare(file,view)));
+        (0..3).map(PaneContent::Ortho).chain(compare).collect()
+    }
+
+    /// the spec of a view of a comparison file, with the slice position clamped to its dimensions
+    fn compare_spec(&self, file:usize, view:usize) -> SliceSpec {
+        let mut spec = self.slice_handler.spec(view);
+        let shape = self.compare_files[file].cfl_buffer.dims.shape();
+        for (i,&n) in spec.fixed.iter_mut().zip(shape.iter()) {
+            *i = (*i).min(n.saturating_sub(1));
+        }
+        spec
+    }
+
+    /// extracts the orthogonal views of every comparison file at the current slice position
+    fn update_compare(&mut self) {
+        for file in 0..self.compare_files.len() {
+            for view in 0..3 {
+                let spec = self.compare_spec(file,view);
+                let compare = &mut self.compare_files[file];
+                let (data,dims) = &mut compare.slices[view];
+                data.resize(spec.slice_dims(&compare.cfl_buffer.dims).numel(),Complex32::ZERO);
+                match spec.extract(&compare.cfl_buffer,data) {
+                    Ok(slice_dims) => *dims = slice_dims,
+                    Err(e) => self.last_error = Some(format!("failed to update {}: {:?}",compare.name,e)),
+                }
+            }
+        }
+    }
+
+    /// combines the primary and comparison slices of the difference view
+    fn update_difference(&mut self) {
+        if !self.panes.contains(&PaneContent::Difference) {
+            return
+        }
+        self.difference_data.clear();
+        self.difference_dims = ArrayDim::from_shape(&[0,0]);
+        let Some(compare) = self.compare_files.get(self.difference_file) else {
+            return
+        };
+        if self.cfl_buffer.dims.shape() != compare.cfl_buffer.dims.shape() {
+            self.last_error = Some(format!("cannot compare {} with different dimensions",compare.name));
+            return
+        }
+        let (a,dims) = self.slice_handler.slice_view(self.difference_view);
+        let (b,_) = &compare.slices[self.difference_view];
+        match self.difference_mode.apply(a,b) {
+            Ok(data) => {
+                self.difference_data = data;
+                self.difference_dims = dims;
+            }
+            Err(e) => self.last_error = Some(format!("difference failed: {:?}",e)),
+        }
+        let center = self.difference_mode.center();
+        let deviations:Vec<f32> = self.difference_data.iter().map(|x| (self.difference_scalar(*x) - center).abs()).collect();
+        let limit = Histogram::new(&deviations,DEFAULT_BINS).percentile(0.99);
+        self.difference_auto_limit = if limit > 0. { limit } else { 1. };
+    }
+
+    /// the signed value of a difference sample shown with the colormap. The magnitude of a complex difference
+    /// has no sign, so the real part is shown unless the imaginary part or phase is viewed
+    fn difference_scalar(&self, x:Complex32) -> f32 {
+        match self.difference_mode {
+            DifferenceMode::MagnitudeDifference | DifferenceMode::PhaseDifference => x.re,
+            DifferenceMode::Difference => match self.view_mode {
+                ViewMode::Im | ViewMode::Phase => scalar(x,self.view_mode),
+                ViewMode::Re | ViewMode::Mag => x.re,
+            },
+            _ => scalar(x,self.view_mode),
+        }
+    }
+
+    /// writes the export pane as currently shown
+    fn export_pane(&self, path:&FilePath) -> Result<(),ViewError> {
+        let (data,dims) = self.pane_data(self.export_pane).ok_or(ViewError::BadIndex(self.export_pane))?;
+        self.write_slice(path,self.export_pane,data,&dims,true)
+    }
+
+    /// writes every slice of the export pane along the export dim to numbered files
+    fn export_batch(&self, path:&FilePath) -> Result<(),ViewError> {
+        let frames:Vec<usize> = (0..self.pane_source(self.export_pane).dims.shape()[self.export_dim.min(N_DIMS - 1)]).collect();
+        let n = frames.len();
+        self.slices_along(self.export_pane,self.export_dim,&frames,|i,data,dims|{
+            self.write_slice(&numbered_path(path,i,n),self.export_pane,data,dims,false)
+        })
+    }
+
+    /// writes one playback cycle of the export pane along the cine dim as an animation
+    fn export_cine(&self, path:&FilePath) -> Result<(),ViewError> {
+        let dim = self.cine.dim;
+        let frames = self.cine.cycle(self.pane_source(self.export_pane).dims.shape()[dim.min(N_DIMS - 1)]);
+        let mut rgba_frames = vec![];
+        let mut size = (0,0);
+        self.slices_along(self.export_pane,dim,&frames,|_,data,dims|{
+            rgba_frames.push(self.pane_rgba(self.export_pane,data));
+            size = (dims.shape()[0],dims.shape()[1]);
+            Ok(())
+        })?;
+        self.animation_format.write(path,&rgba_frames,size.0,size.1,self.cine.fps)
+    }
+
+    /// writes the samples behind the export pane for reading back into BART. Axis-aligned panes keep the
+    /// cfl layout, with the plane and any whole dims taken in full and every other dim at the current
+    /// position. Derived panes are written as the 2-D slice shown
+    fn export_cfl(&self, path:&FilePath) -> Result<(),ViewError> {
+        let pane_id = self.export_pane;
+        let spec = match self.panes.get(pane_id) {
+            Some(PaneContent::Oblique | PaneContent::Difference) => None,
+            _ => self.pane_spec(pane_id),
+        };
+        let Some(spec) = spec else {
+            let (data,dims) = self.pane_data(pane_id).ok_or(ViewError::BadIndex(pane_id))?;
+            return CflBuffer::new(data.to_vec(),dims).write_cfl(path)
+        };
+        let source = self.pane_source(pane_id);
+        let shape = source.dims.shape();
+        let mut start = spec.fixed;
+        let mut end = start.map(|i| i + 1);
+        for dim in 0..N_DIMS {
+            if self.cfl_full_dims[dim] || dim == spec.row_dim || dim == spec.col_dim {
+                start[dim] = 0;
+                end[dim] = shape[dim];
+            }
+        }
+        if let Some((_,roi)) = self.signal_roi.as_ref().filter(|(id,_)| self.cfl_roi_crop && *id == pane_id) {
+            let (h,v) = spec.output_axes();
+            let (lo,hi) = roi.bounds();
+            let pixels = |axis:usize, size:usize| (lo[axis].floor().max(0.) as usize)..(hi[axis].ceil().max(0.) as usize).min(size);
+            // flips and shifts move samples around, so take the range of the source indices under the box
+            let (xs,ys) = (pixels(0,shape[h]),pixels(1,shape[v]));
+            if xs.is_empty() || ys.is_empty() {
+                return Err(ViewError::EmptySelection);
+            }
+            let h_idx:Vec<usize> = xs.map(|x| spec.cfl_index(&source.dims,x,0)[h]).collect();
+            let v_idx:Vec<usize> = ys.map(|y| spec.cfl_index(&source.dims,0,y)[v]).collect();
+            for (dim,indices) in [(h,h_idx),(v,v_idx)] {
+                start[dim] = indices.iter().copied().min().unwrap_or(0);
+                end[dim] = indices.iter().copied().max().unwrap_or(0) + 1;
+            }
+        }
+        source.crop(&start,&end)?.write_cfl(path)
+    }
+
+    /// extracts the slices of a pane at positions along a dimension, in the order given
+    fn slices_along(
+        &self,
+        pane_id:usize,
+        dim:usize,
+        positions:&[usize],
+        mut f:impl FnMut(usize,&[Complex32],&ArrayDim) -> Result<(),ViewError>,
+    ) -> Result<(),ViewError> {
+        if matches!(self.panes.get(pane_id),Some(PaneContent::Oblique | PaneContent::Difference)) {
+            return Err(ViewError::Unsupported("derived panes can't be re-extracted at other positions"));
+        }
+        let spec = self.pane_spec(pane_id).ok_or(ViewError::BadIndex(pane_id))?;
+        if dim >= N_DIMS || dim == spec.row_dim || dim == spec.col_dim {
+            return Err(ViewError::BadIndex(dim));
+        }
+        let source = self.pane_source(pane_id);
+        let mut data = vec![Complex32::ZERO;spec.slice_dims(&source.dims).numel()];
+        for (i,&position) in positions.iter().enumerate() {
+            let mut spec = spec;
+            spec.fixed[dim] = position;
+            let dims = spec.extract(source,&mut data)?;
+            f(i,&data,&dims)?;
+        }
+        Ok(())
+    }
+
+    /// writes the pane grid from a screenshot of the window, leaving out the controls
+    fn export_grid(&self, path:&FilePath, screenshot:&Screenshot) -> Result<(),ViewError> {
+        let x = (CONTROLS_WIDTH * screenshot.scale_factor) as u32;
+        let region = Rectangle {
+            x,
+            y: 0,
+            width: screenshot.size.width.saturating_sub(x),
+            height: screenshot.size.height,
+        };
+        let grid = screenshot.crop(region).map_err(|e| ViewError::Encode(e.to_string()))?;
+        write_png(path,&grid.rgba,grid.size.width as usize,grid.size.height as usize)
+    }
+
+    /// writes a slice of a pane in the export format. Png output is rendered like the pane, with the
+    /// crosshair, regions and profile line drawn on if annotated
+    fn write_slice(&self, path:&FilePath, pane_id:usize, data:&[Complex32], dims:&ArrayDim, annotate:bool) -> Result<(),ViewError> {
+        let (width,height) = (dims.shape()[0],dims.shape()[1]);
+        match self.export_format {
+            ExportFormat::Png => {
+                let mut rgba = self.pane_rgba(pane_id,data);
+                if annotate {
+                    self.annotate(pane_id,&mut rgba,width,height);
+                }
+                write_png(path,&rgba,width,height)
+            }
+            ExportFormat::Tiff16 => {
+                let values:Vec<u16> = data.iter()
+                    .map(|x| (self.window.normalize(self.pane_scalar(pane_id,*x)) * u16::MAX as f32) as u16)
+                    .collect();
+                write_tiff_u16(path,&values,width,height)
+            }
+            ExportFormat::TiffFloat => {
+                let values:Vec<f32> = data.iter().map(|x| self.pane_scalar(pane_id,*x)).collect();
+                write_tiff_f32(path,&values,width,height)
+            }
+        }
+    }
+
+    /// draws the overlays of a pane into its rendered image
+    fn annotate(&self, pane_id:usize, rgba:&mut [u8], width:usize, height:usize) {
+        if let Some((x,y)) = self.pane_crosshair(pane_id) {
+            let yellow = Color::from_rgb(1.,1.,0.).into_rgba8();
+            let (cx,cy) = (x as f32 + 0.5,y as f32 + 0.5);
+            draw_line(rgba,width,height,[0.,cy],[width as f32,cy],yellow);
+            draw_line(rgba,width,height,[cx,0.],[cx,height as f32],yellow);
+        }
+        for (roi,color) in self.pane_rois(pane_id) {
+            draw_polygon(rgba,width,height,&roi.outline(),color.into_rgba8());
+        }
+        if let Some((_,from,to)) = self.profile_line.filter(|(id,..)| *id == pane_id) {
+            draw_line(rgba,width,height,from,to,Color::from_rgb(1.,0.5,0.).into_rgba8());
+        }
+    }
+
+    /// the name drawn in the corner of a pane when comparing files
+    fn pane_label(&self, pane_id:usize) -> String {
+        match self.panes.get(pane_id) {
+            Some(PaneContent::Compare(file,_)) => self.compare_files.get(*file).map(|c| c.name.clone()).unwrap_or_default(),
+            Some(PaneContent::Difference) => self.difference_mode.to_string(),
+            Some(PaneContent::Ortho(_)) if !self.compare_files.is_empty() => self.name.clone(),
+            _ => String::new(),
+        }
+    }
+
+    /// recomputes everything derived from the cfl after its samples change
+    fn reload(&mut self) {
+        self.update_projection();
+        self.refresh();
+        if self.histogram_volume {
+            self.update_histogram();
+        }
+    }
+
+    /// transforms the loaded samples on the rayon pool, or restores them if the transform is off
+    fn start_fft(&mut self) -> Task<ViewPanelMessage> {
+        self.fft_generation += 1;
+        if !self.fft_enabled {
+            self.fft_busy = false;
+            if let Some(raw_data) = self.raw_data.take() {
+                self.cfl_buffer.data = raw_data;
+                self.reload();
+            }
+            return Task::none()
+        }
+        let mut data = self.raw_data.get_or_insert_with(|| self.cfl_buffer.data.clone()).clone();
+        let (fft,dims,generation) = (self.fft,self.cfl_buffer.dims,self.fft_generation);
+        self.fft_busy = true;
+        let (sender,receiver) = oneshot::channel();
+        rayon::spawn(move ||{
+            let result = fft.apply(&mut data,&dims).map(|()| Arc::new(data)).map_err(|e| format!("fft failed: {:?}",e));
+            let _ = sender.send(result);
+        });
+        Task::perform(receiver,move |result|{
+            ViewPanelMessage::FftFinished(generation,result.unwrap_or_else(|_| Err("fft was cancelled".to_string())))
+        })
+    }
+
+    /// sizes the grid to fit all panes
+    fn layout_grid(&mut self) {
+        self.n_panes = self.panes.len();
+        let zoom = if self.zoom_linked { self.pane_zoom.first().copied().unwrap_or_default() } else { PaneZoom::default() };
+        self.pane_zoom.resize(self.n_panes,zoom);
+        self.grid_dims = if self.n_panes <= 3 {
+            [1,self.n_panes.max(1)]
+        }else if self.panes.iter().any(|pane| matches!(pane,PaneContent::Compare(..))) {
+            // one row of orthogonal views per file
+            [self.n_panes.div_ceil(3),3]
+        }else {
+            let cols = (self.n_panes as f32).sqrt().ceil() as usize;
+            [self.n_panes.div_ceil(cols),cols]
+        };
+    }
+
+    fn lightbox_enabled(&self) -> bool {
+        self.panes.iter().any(|pane| matches!(pane,PaneContent::Lightbox(_)))
+    }
+
+    /// extracts the lightbox slices and replaces the lightbox panes to match
+    fn update_lightbox(&mut self) {
+        self.lightbox.spec = self.slice_handler.spec(0);
+        let specs = self.lightbox.specs(&self.cfl_buffer.dims);
+        self.lightbox_total = specs.len();
+        self.lightbox_data = specs.iter().take(MAX_LIGHTBOX_PANES).filter_map(|spec|{
+            let mut data = vec![Complex32::ZERO;spec.slice_dims(&self.cfl_buffer.dims).numel()];
+            spec.extract(&self.cfl_buffer,&mut data).ok().map(|dims| (data,dims))
+        }).collect();
+        let others:Vec<_> = self.panes.iter().copied().filter(|pane| matches!(pane,PaneContent::Oblique | PaneContent::Projection | PaneContent::Difference)).collect();
+        if self.lightbox_data.is_empty() {
+            // nothing to step through, so fall back to the orthogonal views
+            self.panes = self.ortho_panes().into_iter().chain(others).collect();
+        }else {
+            self.panes = (0..self.lightbox_data.len()).map(PaneContent::Lightbox).chain(others).collect();
+        }
+    }
+
+    /// collapses the cfl with the current projection settings and extracts the displayed slice
+    fn update_projection(&mut self) {
+        if !self.panes.contains(&PaneContent::Projection) {
+            return
+        }
+        match self.projection.project(&self.cfl_buffer) {
+            Ok(buffer) => self.projection_buffer = Some(buffer),
+            Err(e) => {
+                self.last_error = Some(format!("projection failed: {:?}",e));
+                self.projection_buffer = None;
+            }
+        }
+        self.update_projection_slice();
+    }
+
+    /// the orthogonal view that doesn't contain the projected dim, or the first view if the projected dim is
+    /// outside the volume
+    fn projection_view(&self) -> usize {
+        self.slice_handler.view_slices.iter()
+            .position(|&d| d == self.projection.dim)
+            .map(|i| (i + 1) % 3)
+            .unwrap_or(0)
+    }
+
+    /// extracts the slice through the projected cfl
+    fn update_projection_slice(&mut self) {
+        let Some(buffer) = self.projection_buffer.as_ref() else {
+            self.projection_data.clear();
+            self.projection_dims = ArrayDim::from_shape(&[0,0]);
+            return
+        };
+        let mut spec = self.slice_handler.spec(self.projection_view());
+        spec.fixed[self.projection.dim] = 0;
+        let n = spec.slice_dims(&buffer.dims).numel();
+        self.projection_data.resize(n,Complex32::ZERO);
+        if let Ok(dims) = spec.extract(buffer,&mut self.projection_data) {
+            self.projection_dims = dims;
+        }
+    }
+
+    /// reslices the oblique plane at the current angles and offset
+    fn update_oblique(&mut self) {
+        let [theta,phi] = self.oblique_angles.map(f32::to_radians);
+        let plane = ObliquePlane {
+            volume_dims: self.slice_handler.view_slices,
+            voxel_size: self.slice_handler.view_slices.map(|dim| self.cfl_buffer.voxel_size[dim]),
+            fixed: self.slice_handler.fixed,
+            ..ObliquePlane::from_angles(theta,phi,self.oblique_plane.offset)
+        };
+        self.oblique_plane = plane;
+        let n = plane.slice_dims(&self.cfl_buffer.dims).numel();
+        self.oblique_data.resize(n,Complex32::ZERO);
+        if let Ok(dims) = plane.extract(&self.cfl_buffer,&mut self.oblique_data) {
+            self.oblique_dims = dims;
+        }
+    }
+
 
 
 }
@@ -109,39 +2025,513 @@ impl Default for ViewMode {
 }
 
 
-fn make_rgba(cfl_data:&[Complex32],view_mode:ViewMode,scaling:f32) -> Vec<u8> {
-    let scalars:Vec<f32> = match view_mode {
-        ViewMode::Re => cfl_data.iter().map(|x|x.re).collect(),
-        ViewMode::Im => cfl_data.iter().map(|x|x.im).collect(),
-        ViewMode::Mag => cfl_data.iter().map(|x|x.norm()).collect(),
-        ViewMode::Phase => cfl_data.iter().map(|x|x.to_polar().1).collect()
-    };
-    scalars.into_iter().map(|x| (x * scaling).clamp(u8::MIN as f32,u8::MAX as f32))
-        .map(|x| x as u8)
-        .map(|x|[x,x,x,u8::MAX])
-        .flatten()
+fn make_rgba(cfl_data:&[Complex32],view_mode:ViewMode,window:&Window) -> Vec<u8> {
+    cfl_data.iter().map(|x| scalar(*x,view_mode))
+        .map(|x| window.display_value(x))
+        .flat_map(|x|[x,x,x,u8::MAX])
         .collect()
 }
 
+/// maps values to a blue-white-red colormap, white at the center and full color at center ± limit
+fn signed_rgba(values:&[f32], center:f32, limit:f32) -> Vec<u8> {
+    let limit = limit.max(f32::EPSILON);
+    values.iter().flat_map(|&x|{
+        let t = ((x - center) / limit).clamp(-1.,1.);
+        let fade = ((1. - t.abs()) * u8::MAX as f32) as u8;
+        if t >= 0. {
+            [u8::MAX,fade,fade,u8::MAX]
+        }else {
+            [fade,fade,u8::MAX,u8::MAX]
+        }
+    }).collect()
+}
+
+/// a component of a complex profile
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileComponent {
+    Mag,
+    Re,
+    Im,
+    Phase,
+}
+
+impl ProfileComponent {
+    const ALL: [ProfileComponent;4] = [ProfileComponent::Mag, ProfileComponent::Re, ProfileComponent::Im, ProfileComponent::Phase];
+
+    fn value(&self, x:Complex32) -> f32 {
+        match self {
+            ProfileComponent::Mag => x.norm(),
+            ProfileComponent::Re => x.re,
+            ProfileComponent::Im => x.im,
+            ProfileComponent::Phase => x.arg(),
+        }
+    }
+
+    fn color(&self) -> Color {
+        match self {
+            ProfileComponent::Mag => Color::WHITE,
+            ProfileComponent::Re => Color::from_rgb(1.,0.,0.),
+            ProfileComponent::Im => Color::from_rgb(0.,0.,1.),
+            ProfileComponent::Phase => Color::from_rgb(0.,1.,0.),
+        }
+    }
+
+    /// phase is plotted on its own axis
+    fn axis_id(&self) -> &'static str {
+        match self {
+            ProfileComponent::Phase => PROFILE_PHASE_ID,
+            _ => PROFILE_Y_ID,
+        }
+    }
+}
 
-// impl<Message> Program<Message> for BlankCanvas {
-//     type State = ();
-//
-//     fn draw(
-//         &self,
-//         _state: &Self::State,
-//         renderer: &Renderer,
-//         _theme: &Theme,
-//         bounds: Rectangle,
-//         _curser:Cursor,
-//     ) -> Vec<Geometry> {
-//
-//         let mut frame = Frame::new(renderer, bounds.size());
-//
-//         // Border so we see the canvas
-//         let rect = canvas::Path::rectangle(Point::ORIGIN, bounds.size());
-//         frame.stroke(&rect, canvas::Stroke::default());
-//
-//         vec![frame.into_geometry()]
-//     }
-// }
\ No newline at end of file
+impl std::fmt::Display for ProfileComponent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProfileComponent::Mag => write!(f, "mag"),
+            ProfileComponent::Re => write!(f, "real"),
+            ProfileComponent::Im => write!(f, "imag"),
+            ProfileComponent::Phase => write!(f, "phase"),
+        }
+    }
+}
+
+/// one component of the profile drawn on the chart
+#[derive(Debug, Clone)]
+struct ProfileSeries {
+    visible: bool,
+    points: Vec<PlotPoint<f64>>,
+    color: Color,
+}
+
+impl ProfileSeries {
+    fn new(component:ProfileComponent) -> ProfileSeries {
+        ProfileSeries {
+            visible: component == ProfileComponent::Mag,
+            points: vec![],
+            color: component.color(),
+        }
+    }
+}
+
+impl PlotData<f64> for ProfileSeries {
+    fn draw(&self, plot: &mut Plot<f64>, _theme: &Theme) {
+        if self.visible {
+            for seg in self.points.windows(2) {
+                plot.add_shape(
+                    Line::new(seg[0], seg[1]).stroke(iced_aksel::Stroke::new(self.color,Measure::Screen(1.)))
+                )
+            }
+        }
+    }
+}
+
+fn profile_chart_state() -> iced_aksel::State<&'static str,f64> {
+    let mut chart_state = iced_aksel::State::new();
+    chart_state.set_axis(
+        PROFILE_X_ID,
+        Axis::new(Linear::new(0.0, 1.0), axis::Position::Bottom)
+            .with_tick_renderer(tick_renderer)
+    );
+    chart_state.set_axis(
+        PROFILE_Y_ID,
+        Axis::new(Linear::new(0.0, 1.0), axis::Position::Left)
+            .with_tick_renderer(tick_renderer)
+    );
+    chart_state.set_axis(
+        PROFILE_PHASE_ID,
+        Axis::new(Linear::new(-std::f64::consts::PI, std::f64::consts::PI), axis::Position::Right)
+            .with_tick_renderer(tick_renderer)
+    );
+    chart_state
+}
+
+/// histogram bars with the window bounds drawn as markers
+#[derive(Debug, Clone, Default)]
+struct HistogramPlot {
+    histogram:Histogram,
+    window:Window,
+    /// range of values shown on the chart
+    domain:[f32;2],
+    /// plot ln(1 + count) so that sparse bins stay visible
+    log:bool,
+}
+
+impl HistogramPlot {
+    fn height(&self, count:usize) -> f64 {
+        if self.log { (count as f64).ln_1p() } else { count as f64 }
+    }
+
+    fn max_height(&self) -> f64 {
+        let max = self.histogram.counts.iter().copied().max().unwrap_or(0);
+        self.height(max).max(1.)
+    }
+}
+
+impl PlotData<f64> for HistogramPlot {
+    fn draw(&self, plot: &mut Plot<f64>, _theme: &Theme) {
+        let stroke = || iced_aksel::Stroke::new(Color::from_rgb(0.6,0.6,0.6),Measure::Screen(1.));
+        let mut last = PlotPoint::new(self.histogram.min as f64,0.);
+        for (bin,&count) in self.histogram.counts.iter().enumerate() {
+            let h = self.height(count);
+            let start = PlotPoint::new(self.histogram.bin_start(bin) as f64,h);
+            let end = PlotPoint::new(self.histogram.bin_start(bin + 1) as f64,h);
+            plot.add_shape(Line::new(last,start).stroke(stroke()));
+            plot.add_shape(Line::new(start,end).stroke(stroke()));
+            last = end;
+        }
+        plot.add_shape(Line::new(last,PlotPoint::new(self.histogram.max as f64,0.)).stroke(stroke()));
+
+        let top = self.max_height();
+        for x in [self.window.lo,self.window.hi] {
+            plot.add_shape(
+                Line::new(PlotPoint::new(x as f64,0.),PlotPoint::new(x as f64,top))
+                    .stroke(iced_aksel::Stroke::new(Color::from_rgb(1.,1.,0.),Measure::Screen(2.)))
+            );
+        }
+    }
+}
+
+fn histogram_chart_state() -> iced_aksel::State<&'static str,f64> {
+    let mut chart_state = iced_aksel::State::new();
+    chart_state.set_axis(
+        HISTOGRAM_X_ID,
+        Axis::new(Linear::new(0.0, 1.0), axis::Position::Bottom)
+            .with_tick_renderer(tick_renderer)
+    );
+    chart_state.set_axis(
+        HISTOGRAM_Y_ID,
+        Axis::new(Linear::new(0.0, 1.0), axis::Position::Left)
+            .with_tick_renderer(tick_renderer)
+    );
+    chart_state
+}
+
+fn tick_renderer(ctx: TickContext<f64, Theme>) -> TickResult {
+    if ctx.tick.level != 0 {
+        return TickResult::default();
+    }
+
+    TickResult {
+        label: Some(ctx.label(format!("{:.3}", ctx.tick.value))),
+        tick_line: Some(ctx.tickline()),
+        ..Default::default()
+    }
+}
+
+/// the largest 1, 2 or 5 times a power of ten that fits in a length
+fn nice_length(max:f32) -> f32 {
+    let magnitude = 10f32.powf(max.log10().floor());
+    [5.,2.,1.].into_iter().map(|m| m * magnitude).find(|&l| l <= max).unwrap_or(magnitude)
+}
+
+/// the real value of a sample shown for a view mode
+fn scalar(sample:Complex32, view_mode:ViewMode) -> f32 {
+    match view_mode {
+        ViewMode::Re => sample.re,
+        ViewMode::Im => sample.im,
+        ViewMode::Mag => sample.norm(),
+        ViewMode::Phase => sample.arg(),
+    }
+}
+
+
+impl PaneCanvas {
+
+    /// screen pixels per image pixel along x and y. Pixels keep their physical aspect ratio, with the
+    /// smaller side taken as one pixel in 1:1 mode
+    fn scale(&self, bounds:Size) -> Vector {
+        let [dx,dy] = self.pixel_size.unwrap_or([1.,1.]);
+        let aspect = Vector::new(dx / dx.min(dy),dy / dx.min(dy));
+        let base = match self.zoom_mode {
+            ZoomMode::Fit => {
+                let w = self.image_size[0].max(1) as f32 * aspect.x;
+                let h = self.image_size[1].max(1) as f32 * aspect.y;
+                (bounds.width / w).min(bounds.height / h)
+            }
+            ZoomMode::Native => 1.,
+        };
+        aspect * (base * self.zoom.zoom)
+    }
+
+    /// where the image is drawn inside the canvas, centered and then panned
+    fn image_rect(&self, bounds:Size) -> Rectangle {
+        self.image_rect_with(bounds,self.scale(bounds),self.zoom.pan)
+    }
+
+    fn image_rect_with(&self, bounds:Size, scale:Vector, pan:Vector) -> Rectangle {
+        let size = Size::new(self.image_size[0] as f32 * scale.x, self.image_size[1] as f32 * scale.y);
+        Rectangle::new(
+            Point::new((bounds.width - size.width) / 2.,(bounds.height - size.height) / 2.) + pan,
+            size,
+        )
+    }
+
+    /// zoom by a factor while keeping the image point under the cursor fixed
+    fn zoomed(&self, bounds:Size, cursor:Point, factor:f32) -> PaneZoom {
+        let zoom = (self.zoom.zoom * factor).clamp(0.1,100.);
+        let rect = self.image_rect(bounds);
+        let scale = self.scale(bounds);
+        let new_scale = scale * (zoom / self.zoom.zoom);
+        // image coordinates under the cursor
+        let u = (cursor.x - rect.x) / scale.x;
+        let v = (cursor.y - rect.y) / scale.y;
+        let centered = self.image_rect_with(bounds,new_scale,Vector::new(0.,0.));
+        PaneZoom {
+            zoom,
+            pan: Vector::new(cursor.x - u * new_scale.x - centered.x,cursor.y - v * new_scale.y - centered.y),
+        }
+    }
+
+    /// the continuous image coordinates of a point in canvas coordinates
+    fn to_image(&self, bounds:Size, point:Point) -> [f32;2] {
+        let rect = self.image_rect(bounds);
+        let scale = self.scale(bounds);
+        [(point.x - rect.x) / scale.x,(point.y - rect.y) / scale.y]
+    }
+
+    /// the canvas coordinates of continuous image coordinates
+    fn to_canvas(&self, bounds:Size, p:[f32;2]) -> Point {
+        let rect = self.image_rect(bounds);
+        let scale = self.scale(bounds);
+        Point::new(rect.x + p[0] * scale.x,rect.y + p[1] * scale.y)
+    }
+
+    /// closed path through image points
+    fn outline_path(&self, bounds:Size, points:&[[f32;2]]) -> Path {
+        Path::new(|builder|{
+            for (i,&p) in points.iter().enumerate() {
+                let p = self.to_canvas(bounds,p);
+                if i == 0 { builder.move_to(p) } else { builder.line_to(p) }
+            }
+            builder.close();
+        })
+    }
+
+    /// the image pixel under a point in canvas coordinates
+    fn pixel_at(&self, bounds:Size, point:Point) -> Option<(usize,usize)> {
+        let rect = self.image_rect(bounds);
+        if self.image_size.contains(&0) || !rect.contains(point) {
+            return None
+        }
+        let x = ((point.x - rect.x) / rect.width * self.image_size[0] as f32) as usize;
+        let y = ((point.y - rect.y) / rect.height * self.image_size[1] as f32) as usize;
+        Some((x.min(self.image_size[0] - 1),y.min(self.image_size[1] - 1)))
+    }
+
+    /// draws a bar of a round physical length in the bottom left corner
+    fn draw_scale_bar(&self, frame:&mut Frame, bounds:Size, mm_per_px:f32) {
+        if !(mm_per_px > 0. && mm_per_px.is_finite()) {
+            return
+        }
+        let length_mm = nice_length(bounds.width / 5. * mm_per_px);
+        let length_px = length_mm / mm_per_px;
+        let start = Point::new(10.,bounds.height - 10.);
+        let stroke = Stroke::default().with_color(Color::WHITE).with_width(2.);
+        frame.stroke(&Path::line(start,Point::new(start.x + length_px,start.y)),stroke);
+        frame.fill_text(canvas::Text {
+            content: format!("{} mm",length_mm),
+            position: Point::new(start.x,start.y - 18.),
+            color: Color::WHITE,
+            size: 12.into(),
+            ..canvas::Text::default()
+        });
+    }
+
+    /// the canvas coordinates of the center of an image pixel
+    fn pixel_center(&self, bounds:Size, x:usize, y:usize) -> Point {
+        let rect = self.image_rect(bounds);
+        Point::new(
+            rect.x + (x as f32 + 0.5) / self.image_size[0] as f32 * rect.width,
+            rect.y + (y as f32 + 0.5) / self.image_size[1] as f32 * rect.height,
+        )
+    }
+}
+
+impl Program<ViewPanelMessage> for PaneCanvas {
+    type State = PaneState;
+
+    fn update(
+        &self,
+        state: &mut Self::State,
+        event: &Event,
+        bounds: Rectangle,
+        cursor: Cursor,
+    ) -> Option<Action<ViewPanelMessage>> {
+        match event {
+            Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) if self.roi_tool != RoiTool::Off => {
+                let point = cursor.position_in(bounds)?;
+                state.drawing = Some(vec![self.to_image(bounds.size(),point)]);
+                Some(Action::capture())
+            }
+            Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) => {
+                let points = state.drawing.take()?;
+                if self.roi_tool == RoiTool::Line {
+                    let (from,to) = (*points.first()?,*points.last()?);
+                    return Some(Action::publish(ViewPanelMessage::LineDrawn(self.pane_id,from,to)).and_capture())
+                }
+                let roi = self.roi_tool.shape(&points)?;
+                Some(Action::publish(ViewPanelMessage::RoiDrawn(self.pane_id,roi)).and_capture())
+            }
+            Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) => {
+                let point = cursor.position_in(bounds)?;
+                let pixel = self.pixel_at(bounds.size(),point)?;
+                Some(Action::publish(ViewPanelMessage::PaneClicked(self.pane_id,pixel)).and_capture())
+            }
+            Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Right | mouse::Button::Middle)) => {
+                state.pan_from = Some(cursor.position_in(bounds)?);
+                Some(Action::capture())
+            }
+            Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Right | mouse::Button::Middle)) => {
+                state.pan_from.take().map(|_| Action::capture())
+            }
+            Event::Mouse(mouse::Event::WheelScrolled { delta }) => {
+                let point = cursor.position_in(bounds)?;
+                let y = match delta {
+                    mouse::ScrollDelta::Lines { y, .. } | mouse::ScrollDelta::Pixels { y, .. } => *y,
+                };
+                let factor = if y > 0. { 1.1 } else { 1. / 1.1 };
+                let zoom = self.zoomed(bounds.size(),point,factor);
+                Some(Action::publish(ViewPanelMessage::PaneZoomed(self.pane_id,zoom)).and_capture())
+            }
+            Event::Mouse(mouse::Event::CursorMoved { .. }) => {
+                if let Some(points) = state.drawing.as_mut() {
+                    let point = cursor.position_from(bounds.position())?;
+                    let p = self.to_image(bounds.size(),point);
+                    match self.roi_tool {
+                        RoiTool::Freehand => points.push(p),
+                        _ => {
+                            points.truncate(1);
+                            points.push(p);
+                        }
+                    }
+                    return Some(Action::request_redraw())
+                }
+                if let Some(from) = state.pan_from {
+                    let to = cursor.position_from(bounds.position())?;
+                    state.pan_from = Some(to);
+                    let zoom = PaneZoom { pan: self.zoom.pan + (to - from), ..self.zoom };
+                    return Some(Action::publish(ViewPanelMessage::PaneZoomed(self.pane_id,zoom)))
+                }
+                let pixel = cursor.position_in(bounds).and_then(|point| self.pixel_at(bounds.size(),point));
+                // only report changes so panes the cursor isn't over stay quiet
+                if pixel == state.hovered {
+                    return None
+                }
+                state.hovered = pixel;
+                Some(Action::publish(ViewPanelMessage::PaneHovered(self.pane_id,pixel)))
+            }
+            _ => None,
+        }
+    }
+
+    fn draw(
+        &self,
+        state: &Self::State,
+        renderer: &Renderer,
+        _theme: &Theme,
+        bounds: Rectangle,
+        _cursor: Cursor,
+    ) -> Vec<Geometry> {
+
+        let mut frame = Frame::new(renderer, bounds.size());
+
+        let rect = self.image_rect(bounds.size());
+        frame.with_clip(Rectangle::with_size(bounds.size()),|frame|{
+            frame.draw_image(rect, canvas::Image::new(self.handle.clone()).filter_method(self.filter_method));
+
+            if let Some((x,y)) = self.crosshair {
+                let center = self.pixel_center(bounds.size(),x,y);
+                let stroke = Stroke::default().with_color(Color::from_rgb(1.,1.,0.)).with_width(1.);
+                frame.stroke(&Path::line(Point::new(rect.x,center.y),Point::new(rect.x + rect.width,center.y)),stroke);
+                frame.stroke(&Path::line(Point::new(center.x,rect.y),Point::new(center.x,rect.y + rect.height)),stroke);
+            }
+
+            for (roi,color) in &self.rois {
+                let stroke = Stroke::default().with_color(*color).with_width(1.5);
+                frame.stroke(&self.outline_path(bounds.size(),&roi.outline()),stroke);
+            }
+
+            if let Some(roi) = state.drawing.as_ref().and_then(|points| self.roi_tool.shape(points)) {
+                let stroke = Stroke::default().with_color(Color::from_rgb(0.,1.,1.)).with_width(1.);
+                frame.stroke(&self.outline_path(bounds.size(),&roi.outline()),stroke);
+            }
+
+            let line = match (self.roi_tool,state.drawing.as_ref()) {
+                (RoiTool::Line,Some(points)) if points.len() > 1 => Some((points[0],points[points.len() - 1])),
+                _ => self.profile_line,
+            };
+            if let Some((from,to)) = line {
+                let stroke = Stroke::default().with_color(Color::from_rgb(1.,0.5,0.)).with_width(1.5);
+                frame.stroke(&Path::line(self.to_canvas(bounds.size(),from),self.to_canvas(bounds.size(),to)),stroke);
+            }
+
+            if let Some([dx,_]) = self.pixel_size {
+                self.draw_scale_bar(frame,bounds.size(),dx / self.scale(bounds.size()).x);
+            }
+
+            if !self.label.is_empty() {
+                frame.fill_text(canvas::Text {
+                    content: self.label.clone(),
+                    position: Point::new(6.,6.),
+                    color: Color::WHITE,
+                    size: 12.into(),
+                    ..canvas::Text::default()
+                });
+            }
+        });
+
+        vec![frame.into_geometry()]
+    }
+
+    fn mouse_interaction(
+        &self,
+        state: &Self::State,
+        bounds: Rectangle,
+        cursor: Cursor,
+    ) -> mouse::Interaction {
+        if state.pan_from.is_some() {
+            mouse::Interaction::Grabbing
+        }else if cursor.is_over(bounds) {
+            mouse::Interaction::Crosshair
+        }else {
+            mouse::Interaction::default()
+        }
+    }
+}
+
+async fn pick_cfl_files() -> Option<Vec<PathBuf>> {
+    let start_dir = std::env::current_dir().ok();
+    let dialog = FileDialog::new()
+        .add_filter("cfl files", &["cfl", "hdr"])
+        .add_filter("nifti files", &["nii", "gz"])
+        .add_filter("numpy files", &["npy", "npz"])
+        .add_filter("matlab files", &["mat"])
+        .add_filter("ismrmrd files", &["h5"]);
+    match start_dir {
+        Some(start_dir) => dialog.set_directory(start_dir).pick_files(),
+        None => dialog.pick_files(),
+    }
+}
+
+async fn pick_raw_files() -> Option<Vec<PathBuf>> {
+    match std::env::current_dir() {
+        Ok(start_dir) => FileDialog::new().set_directory(start_dir).pick_files(),
+        Err(_) => FileDialog::new().pick_files(),
+    }
+}
+
+async fn pick_dicom_folder() -> Option<PathBuf> {
+    match std::env::current_dir() {
+        Ok(start_dir) => FileDialog::new().set_directory(start_dir).pick_folder(),
+        Err(_) => FileDialog::new().pick_folder(),
+    }
+}
+
+async fn pick_save_path(extension:&'static str) -> Option<PathBuf> {
+    let dialog = FileDialog::new().add_filter(extension, &[extension]);
+    match std::env::current_dir() {
+        Ok(start_dir) => dialog.set_directory(start_dir).save_file(),
+        Err(_) => dialog.save_file(),
+    }
+}